@@ -0,0 +1,65 @@
+use crate::parse::parse_decimal;
+use chrono::{DateTime, Duration, NaiveDateTime};
+use std::fs;
+use std::io;
+
+/// Reads the boot time of the local machine from /proc/stat.
+pub fn boot_time() -> io::Result<NaiveDateTime> {
+    let stat = fs::read_to_string("/proc/stat")?;
+    parse_btime(&stat)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no btime in /proc/stat"))
+}
+
+/// Parses the "btime" line of /proc/stat, which gives the boot time in seconds since the Unix
+/// epoch.
+fn parse_btime(stat: &str) -> Option<NaiveDateTime> {
+    let btime = stat.lines().find_map(|line| line.strip_prefix("btime "))?;
+    let t = DateTime::from_timestamp(btime.trim().parse().ok()?, 0)?;
+    Some(t.naive_utc())
+}
+
+/// Computes the boot time from an anchor: the time since boot `uptime`, in (fractional) seconds,
+/// was observed at time `t`.
+pub fn boot_time_from_anchor(uptime: &str, t: NaiveDateTime) -> Option<NaiveDateTime> {
+    let (sec, nsec) = parse_decimal(uptime)?;
+    t.checked_sub_signed(Duration::try_seconds(sec)?)?
+        .checked_sub_signed(Duration::nanoseconds(nsec.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, NaiveTime};
+
+    #[test]
+    fn test_parse_btime() {
+        let stat = "cpu  1 2 3 4\nintr 1 2\nctxt 123\nbtime 1647770102\nprocesses 42\n";
+        assert_eq!(
+            parse_btime(stat),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2022, 3, 20).unwrap(),
+                NaiveTime::from_hms_opt(9, 55, 2).unwrap()
+            ))
+        );
+        assert_eq!(parse_btime("cpu  1 2 3 4\n"), None);
+        assert_eq!(parse_btime("btime foo\n"), None);
+    }
+
+    #[test]
+    fn test_boot_time_from_anchor() {
+        let t = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2022, 3, 20).unwrap(),
+            NaiveTime::from_hms_opt(10, 0, 0).unwrap(),
+        );
+        assert_eq!(
+            boot_time_from_anchor("3600.5", t),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2022, 3, 20).unwrap(),
+                NaiveTime::from_hms_milli_opt(8, 59, 59, 500).unwrap()
+            ))
+        );
+        assert_eq!(boot_time_from_anchor("abc", t), None);
+        assert_eq!(boot_time_from_anchor("99999999999999999.0", t), None);
+        assert_eq!(boot_time_from_anchor("9223372036854775", t), None);
+    }
+}
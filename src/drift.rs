@@ -0,0 +1,92 @@
+use chrono::{Duration, NaiveDateTime};
+
+/// Linear correction of timestamps from a drifting clock: time `observed` maps to `actual`, and
+/// intervals from it are multiplied by `scale`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DriftCorrection {
+    pub observed: NaiveDateTime,
+    pub actual: NaiveDateTime,
+    pub scale: f64,
+}
+
+impl DriftCorrection {
+    /// Correction for a clock that runs fast by `ppm` parts per million, or slow if negative, and
+    /// shows the correct time at `t`.
+    pub fn from_drift(t: NaiveDateTime, ppm: f64) -> DriftCorrection {
+        DriftCorrection {
+            observed: t,
+            actual: t,
+            scale: 1.0 / (1.0 + ppm * 1e-6),
+        }
+    }
+
+    /// Correction from two anchors, pairs of observed and actual time. Returns None if the
+    /// observed times are equal.
+    pub fn from_anchors(
+        (observed1, actual1): (NaiveDateTime, NaiveDateTime),
+        (observed2, actual2): (NaiveDateTime, NaiveDateTime),
+    ) -> Option<DriftCorrection> {
+        let observed = (observed2 - observed1).num_nanoseconds()?;
+        let actual = (actual2 - actual1).num_nanoseconds()?;
+        if observed == 0 {
+            return None;
+        }
+        Some(DriftCorrection {
+            observed: observed1,
+            actual: actual1,
+            scale: actual as f64 / observed as f64,
+        })
+    }
+
+    /// Corrects observed time `t`. Returns None on overflow.
+    pub fn apply(&self, t: NaiveDateTime) -> Option<NaiveDateTime> {
+        let ns = (t - self.observed).num_nanoseconds()?;
+        let ns = (ns as f64 * self.scale).round() as i64;
+        self.actual.checked_add_signed(Duration::nanoseconds(ns))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, NaiveTime};
+
+    fn at(h: u32, m: u32, s: u32, ms: u32) -> NaiveDateTime {
+        NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2022, 3, 20).unwrap(),
+            NaiveTime::from_hms_milli_opt(h, m, s, ms).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_from_drift() {
+        // 50 ppm fast clock gains 180 ms in an hour.
+        let c = DriftCorrection::from_drift(at(10, 0, 0, 0), 50.0);
+        assert_eq!(c.apply(at(10, 0, 0, 0)), Some(at(10, 0, 0, 0)));
+        assert_eq!(c.apply(at(11, 0, 0, 180)), Some(at(11, 0, 0, 0)));
+        assert_eq!(c.apply(at(8, 59, 59, 820)), Some(at(9, 0, 0, 0)));
+        let c = DriftCorrection::from_drift(at(10, 0, 0, 0), -50.0);
+        assert_eq!(c.apply(at(10, 59, 59, 820)), Some(at(11, 0, 0, 0)));
+    }
+
+    #[test]
+    fn test_from_anchors() {
+        // 2 s behind at 10:00, and 1 s behind at 12:00.
+        let c = DriftCorrection::from_anchors(
+            (at(10, 0, 0, 0), at(10, 0, 2, 0)),
+            (at(12, 0, 0, 0), at(12, 0, 1, 0)),
+        )
+        .unwrap();
+        assert_eq!(c.apply(at(10, 0, 0, 0)), Some(at(10, 0, 2, 0)));
+        assert_eq!(c.apply(at(11, 0, 0, 0)), Some(at(11, 0, 1, 500)));
+        assert_eq!(c.apply(at(12, 0, 0, 0)), Some(at(12, 0, 1, 0)));
+        assert_eq!(c.apply(at(14, 0, 0, 0)), Some(at(14, 0, 0, 0)));
+        assert_eq!(
+            DriftCorrection::from_anchors(
+                (at(10, 0, 0, 0), at(10, 0, 2, 0)),
+                (at(10, 0, 0, 0), at(10, 0, 3, 0)),
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,24 @@
+use std::fmt;
+
+/// Error in converting a timestamp.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Time difference is too large to be expressed in nanoseconds, i.e. over about 292 years.
+    DeltaOverflow,
+    /// Number of fractional digits is over 9.
+    InvalidPrecision(usize),
+    /// Custom format is not a valid strftime format.
+    InvalidFormat(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::DeltaOverflow => write!(f, "Too large delta"),
+            Error::InvalidPrecision(n) => write!(f, "Invalid precision {}", n),
+            Error::InvalidFormat(fmt) => write!(f, "Invalid format {:?}", fmt),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -1,5 +1,15 @@
+mod boot;
+mod drift;
+mod error;
+mod locate;
 mod parse;
 mod write;
+mod zone;
 
+pub use boot::*;
+pub use drift::*;
+pub use error::*;
+pub use locate::*;
 pub use parse::*;
 pub use write::*;
+pub use zone::*;
@@ -0,0 +1,287 @@
+use regex::Regex;
+use std::ops::Range;
+
+/// Maximum number of whitespace-separated words in a timestamp.
+const MAX_TIMESTAMP_WORDS: usize = 5;
+
+/// Opening and closing brackets that may enclose a timestamp.
+const BRACKETS: [(char, char); 4] = [('[', ']'), ('(', ')'), ('<', '>'), ('{', '}')];
+
+/// Where to look for the timestamp in a line.
+#[derive(Clone, Debug, Default)]
+pub enum Locator {
+    /// In the beginning of the line, or for syslog formats after a syslog header ("<PRI>"
+    /// optionally followed by "VERSION ").
+    #[default]
+    Start,
+    /// Text matched by the first capture group of the regex, or by the whole regex if it has no
+    /// groups.
+    Regex(Regex),
+    /// Field number `index` (counting from 1) when the line is split at `delimiter`. Without a
+    /// delimiter fields are separated by whitespace, and the timestamp may span several fields.
+    Field {
+        index: usize,
+        delimiter: Option<char>,
+    },
+}
+
+impl PartialEq for Locator {
+    fn eq(&self, other: &Locator) -> bool {
+        match (self, other) {
+            (Locator::Start, Locator::Start) => true,
+            (Locator::Regex(a), Locator::Regex(b)) => a.as_str() == b.as_str(),
+            (
+                Locator::Field { index, delimiter },
+                Locator::Field {
+                    index: other_index,
+                    delimiter: other_delimiter,
+                },
+            ) => index == other_index && delimiter == other_delimiter,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Locator {}
+
+fn is_blank(c: u8) -> bool {
+    c == b' ' || c == b'\t'
+}
+
+/// Returns the possible end positions of a timestamp at the beginning of the string, shortest
+/// first.
+///
+/// The timestamp must not start with whitespace (space or tab), may consist of up to
+/// MAX_TIMESTAMP_WORDS words, and must be followed by whitespace, or by the end of the string if
+/// `to_end` is set.
+fn timestamp_ends(s: &str, to_end: bool) -> impl Iterator<Item = usize> + '_ {
+    let b = s.as_bytes();
+    let words = match b.first() {
+        Some(&c) if !is_blank(c) => MAX_TIMESTAMP_WORDS,
+        _ => 0,
+    };
+    (1..=b.len())
+        .filter(move |&i| b.get(i).map_or(to_end, |&c| is_blank(c)) && !is_blank(b[i - 1]))
+        .take(words)
+}
+
+/// Returns the length of a syslog header, "<PRI>" optionally followed by "VERSION ", at the
+/// beginning of the line.
+fn syslog_header_len(s: &str) -> Option<usize> {
+    let pri = s.strip_prefix('<')?.split_once('>')?.0;
+    if !(1..=3).contains(&pri.len()) || !pri.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let len = pri.len() + 2;
+    let version = s[len..].bytes().take_while(u8::is_ascii_digit).count();
+    match (1..=2).contains(&version) && s[len + version..].starts_with(' ') {
+        true => Some(len + version + 1),
+        false => Some(len),
+    }
+}
+
+/// Returns the start position of a whitespace-separated field (counting from 1).
+fn word_start(s: &str, index: usize) -> Option<usize> {
+    let b = s.as_bytes();
+    (0..b.len())
+        .filter(|&i| !is_blank(b[i]) && (i == 0 || is_blank(b[i - 1])))
+        .nth(index.checked_sub(1)?)
+}
+
+/// Returns the span of a delimited field (counting from 1).
+fn field_span(s: &str, index: usize, delimiter: char) -> Option<Range<usize>> {
+    let mut start = 0;
+    for (n, field) in s.split(delimiter).enumerate() {
+        if n + 1 == index {
+            return Some(start..start + field.len());
+        }
+        start += field.len() + delimiter.len_utf8();
+    }
+    None
+}
+
+/// Narrows the span to exclude enclosing brackets and any whitespace inside them, e.g.
+/// "[   12.345678]" to "12.345678".
+fn strip_brackets(s: &str, span: Range<usize>) -> Range<usize> {
+    let text = &s[span.clone()];
+    for (open, close) in BRACKETS {
+        if let Some(inner) = text.strip_prefix(open).and_then(|t| t.strip_suffix(close)) {
+            let start = span.end - 1 - inner.trim_start_matches([' ', '\t']).len();
+            let end = span.start + 1 + inner.trim_end_matches([' ', '\t']).len();
+            return start..end.max(start);
+        }
+    }
+    span
+}
+
+impl Locator {
+    /// Returns the spans of the line that may contain the timestamp, in order of preference.
+    ///
+    /// Brackets enclosing the timestamp are not included in the spans. If `syslog_header` is set,
+    /// a timestamp after a syslog header is preferred.
+    pub(crate) fn candidates(&self, s: &str, syslog_header: bool) -> Vec<Range<usize>> {
+        let spans: Vec<Range<usize>> = match self {
+            Locator::Start => {
+                let start = match syslog_header {
+                    true => syslog_header_len(s).unwrap_or(s.len()),
+                    false => s.len(),
+                };
+                timestamp_ends(&s[start..], false)
+                    .map(|end| start..start + end)
+                    .chain(timestamp_ends(s, false).map(|end| 0..end))
+                    .collect()
+            }
+            Locator::Regex(re) => re
+                .captures(s)
+                .and_then(|c| c.get(1).or_else(|| c.get(0)))
+                .map(|m| m.range())
+                .into_iter()
+                .collect(),
+            Locator::Field {
+                index,
+                delimiter: None,
+            } => match word_start(s, *index) {
+                // The field may be the last one in the line.
+                Some(start) => timestamp_ends(&s[start..], true)
+                    .map(|end| start..start + end)
+                    .collect(),
+                None => vec![],
+            },
+            Locator::Field {
+                index,
+                delimiter: Some(delimiter),
+            } => field_span(s, *index, *delimiter).into_iter().collect(),
+        };
+        spans
+            .into_iter()
+            .map(|span| strip_brackets(s, span))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_start() {
+        assert_eq!(
+            Locator::Start.candidates("2022-03-20 15:32:01 Log message", false),
+            vec![0..10, 0..19, 0..23]
+        );
+        assert!(Locator::Start.candidates(" Logmessage", false).is_empty());
+        assert!(Locator::Start.candidates("Logmessage", false).is_empty());
+        assert!(Locator::Start.candidates("", false).is_empty());
+    }
+
+    #[test]
+    fn test_syslog_header() {
+        assert_eq!(
+            Locator::Start.candidates("<13>1 2022-03-20T15:32:01Z host app", true),
+            vec![6..26, 6..31, 0..5, 0..26, 0..31]
+        );
+        assert_eq!(
+            Locator::Start.candidates("<34>Oct 11 22:14:15 host", true),
+            vec![4..7, 4..10, 4..19, 0..7, 0..10, 0..19]
+        );
+        // Other formats don't look past the header.
+        assert_eq!(
+            Locator::Start.candidates("<13>1 2022-03-20T15:32:01Z host app", false),
+            vec![0..5, 0..26, 0..31]
+        );
+        assert_eq!(syslog_header_len("<13>1 x"), Some(6));
+        assert_eq!(syslog_header_len("<191>10 x"), Some(8));
+        assert_eq!(syslog_header_len("<34>Oct"), Some(4));
+        assert_eq!(syslog_header_len("<1650400500> x"), None);
+        assert_eq!(syslog_header_len("<> x"), None);
+        assert_eq!(syslog_header_len("<a> x"), None);
+        assert_eq!(syslog_header_len("13>1 x"), None);
+    }
+
+    #[test]
+    fn test_brackets() {
+        assert_eq!(
+            Locator::Start.candidates("[2022-03-20 15:32:01.462] Log message", false),
+            vec![0..11, 1..24, 0..29]
+        );
+        assert_eq!(
+            Locator::Start.candidates("[   12.345678] Log", false),
+            vec![0..1, 4..13]
+        );
+        assert_eq!(
+            Locator::Start.candidates("(1650400500.123456) x", false),
+            vec![1..18]
+        );
+        assert_eq!(
+            Locator::Start.candidates("<1650400500> x", false),
+            vec![1..11]
+        );
+        assert_eq!(Locator::Start.candidates("{12} x", false), vec![1..3]);
+        assert_eq!(Locator::Start.candidates("[  ] x", false), vec![0..1, 3..3]);
+        // Mismatched brackets are not stripped.
+        assert_eq!(Locator::Start.candidates("[12) x", false), vec![0..4]);
+        assert_eq!(
+            Locator::Start.candidates("<13>1 2022-03-20T15:32:01Z", false),
+            vec![0..5]
+        );
+    }
+
+    #[test]
+    fn test_regex() {
+        let re = Regex::new(r"^<\d+>1 (\S+) ").unwrap();
+        assert_eq!(
+            Locator::Regex(re).candidates("<13>1 2022-03-20T15:32:01Z host", false),
+            vec![6..26]
+        );
+        let re = Regex::new(r"\d{4}-\d\d-\d\d").unwrap();
+        assert_eq!(
+            Locator::Regex(re.clone()).candidates("[host-3] 2022-03-20 x", false),
+            vec![9..19]
+        );
+        assert!(Locator::Regex(re)
+            .candidates("[host-3] x", false)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_whitespace_field() {
+        let locator = Locator::Field {
+            index: 2,
+            delimiter: None,
+        };
+        assert_eq!(
+            locator.candidates("INFO  2022-03-20 15:32:01 msg", false),
+            vec![6..16, 6..25, 6..29]
+        );
+        assert_eq!(
+            locator.candidates("  INFO 12.5 msg", false),
+            vec![7..11, 7..15]
+        );
+        assert_eq!(locator.candidates("INFO 1647790321", false), vec![5..15]);
+        assert_eq!(
+            locator.candidates("INFO 2022-03-20 15:32:01", false),
+            vec![5..15, 5..24]
+        );
+        assert_eq!(locator.candidates("INFO 12.5 \t", false), vec![5..9]);
+        assert!(locator.candidates("INFO", false).is_empty());
+        let locator = Locator::Field {
+            index: 0,
+            delimiter: None,
+        };
+        assert!(locator.candidates("INFO 12.5 msg", false).is_empty());
+    }
+
+    #[test]
+    fn test_delimited_field() {
+        let locator = Locator::Field {
+            index: 2,
+            delimiter: Some(';'),
+        };
+        assert_eq!(
+            locator.candidates("INFO;2022-03-20 15:32:01;msg", false),
+            vec![5..24]
+        );
+        assert_eq!(locator.candidates("INFO;", false), vec![5..5]);
+        assert!(locator.candidates("INFO", false).is_empty());
+    }
+}
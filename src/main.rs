@@ -1,33 +1,71 @@
-use clap::Parser;
-use khronos::{self, InputFormat, OutputFormat, Precision, Unit};
-use std::io::{self, BufRead};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, Utc};
+use clap::{CommandFactory, ErrorKind, Parser};
+use khronos::{
+    self, DriftCorrection, DstPolicy, InputFormat, Locator, OutputFormat, ParseOptions, Precision,
+    Unit, Zone,
+};
+use regex::Regex;
+use std::fs::{self, File};
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+use std::{fmt, mem, process, slice, str};
 
 /// Log timestamp rewriter
 ///
-/// Reads from stdin lines and rewrites their timestamps. The timestamp must
-/// be at the start of line and separated from the message by at least one
-/// space. If the timestamp of a line cannot be successfully parsed, the line
-/// is output as-is.
+/// Reads lines from files, or stdin, and rewrites their timestamps. By default the
+/// timestamp must be at the start of line, or in syslog formats after a <PRI> header,
+/// and separated from the message by at least one space. The timestamp itself may contain spaces, e.g.
+/// "2022-03-20 15:32:01", or be enclosed in brackets, e.g. "[12.345678]".
+/// Timestamps elsewhere in the line can be located with --regex or --field. If
+/// the timestamp of a line cannot be successfully parsed, the line is output
+/// as-is.
 ///
 /// If input format is not given it is automatically deduced from input.
 /// In this case the lines are read and output as-is until the first
 /// recognizable timestamp is met.
 #[derive(Parser, Debug)]
 #[clap(after_help = r"INPUT FORMATS:
-    iso     ISO 8601
-    unix    Unix time in (fractional) seconds
-    unixms  Unix time in (fractional) milliseconds
+    iso             ISO 8601, optionally with UTC offset (Z, +HH:MM, +HHMM)
+    unix            Unix time in (fractional) seconds
+    unixms          Unix time in (fractional) milliseconds
+    unixus          Unix time in (fractional) microseconds
+    unixns          Unix time in (fractional) nanoseconds
+    since:<TIME>    (Fractional) seconds since given ISO 8601 time, e.g.
+                    since:2022-03-20T00:00:00. TIME is in --intz unless it has
+                    an UTC offset.
+    dmesg[:<BOOT>]  Kernel time, i.e. seconds since boot, e.g. [ 12.345678].
+                    BOOT is either the boot time in ISO 8601, like TIME above
+                    (e.g. from `uptime -s`), or an anchor <SECONDS>@<TIME>
+                    telling that SECONDS since boot was TIME. If BOOT is not
+                    given, the boot time of this machine is read from
+                    /proc/stat.
+    syslog          BSD syslog (RFC 3164), e.g. Mar 20 15:32:01. See --year.
+    rfc5424         RFC 5424 syslog, i.e. RFC 3339 with mandatory UTC offset.
+    clf             Apache/nginx Common Log Format, e.g.
+                    [20/Mar/2022:15:32:01 +0000]. Located in the fourth field
+                    unless --regex or --field is given.
+    custom:<FMT>    strftime format, e.g. custom:%d/%m/%Y-%H:%M:%S
 
 OUTPUT FORMATS:
-    iso     ISO 8601. Options: precision, nodate
+    iso     ISO 8601. Options: precision, nodate, zone, offset, comma
     unix    Unix time. Options: units, precision
-    delta   Time since previous line. Options: units, precision
-    elapsed Time since log start. Options: units, precision
+    delta   Time since previous line, negative if out of order.
+            Options: units, precision, mark
+    elapsed Time since log start, or since --zero or --zero-line.
+            Options: units, precision
+    rfc5424 RFC 5424 syslog with microseconds and UTC offset. Options: zone
+    fmt     strftime format, given after a colon: fmt[,OPTION...]:<FMT>.
+            Options: zone
 
 OUTPUT OPTIONS:
     precision   .0 | .1 | .2 | ... | .9
     units       s | ms | us | ns
     nodate      nodate
+    zone        utc | local | tz=<IANA time zone, e.g. Europe/Helsinki>
+    offset      offset
+    comma       comma (decimal comma instead of dot, e.g. 15:32:01,462)
+    mark        mark (prefix negative deltas with !)
 
 EXAMPLES:
     Specify unix time in milliseconds with 3 fractional digits:
@@ -35,16 +73,127 @@ EXAMPLES:
 
     Specify delta in seconds with 6 fractional digits:
         delta,.6
+
+    Specify ISO 8601 in Helsinki time, with UTC offset:
+        iso,tz=Europe/Helsinki,offset
+
+    Specify syslog style local time:
+        fmt,local:%b %d %H:%M:%S
 ")]
 struct Args {
     /// Input format. Auto-detect if not specified.
+    #[clap(short, long, value_name = "FMT")]
+    informat: Option<String>,
+
+    /// Time zone of input timestamps that have no UTC offset: utc, local or an IANA time zone
+    /// name, e.g. Europe/Helsinki.
+    #[clap(long, value_name = "ZONE", default_value = "utc")]
+    intz: Zone,
+
+    /// How to interpret input times that are ambiguous or skipped due to daylight saving time
+    /// transitions: earliest, latest or reject. Rejected timestamps are output as-is.
+    #[clap(long, value_name = "POLICY", default_value = "earliest")]
+    dst: DstPolicy,
+
+    /// Year of the first timestamp, for input formats without a year. The year is advanced when
+    /// the timestamps wrap around at new year. If not given, the timestamps are assumed to be
+    /// before the modification time of the input file, or before the current time when reading
+    /// from a pipe.
+    #[clap(long, value_name = "YEAR")]
+    year: Option<i32>,
+
+    /// Add the given duration to every timestamp, e.g. +3.25s or -1h2m. Units are d, h, m, s, ms,
+    /// us and ns. With FILE=, only to the timestamps of input FILE, e.g. b.log=-2s. Can be given
+    /// for several inputs.
     #[clap(
-        short,
         long,
-        value_name="FMT",
-        parse(try_from_str=parse_input_format),
+        value_name = "[FILE=]DURATION",
+        allow_hyphen_values = true,
+        multiple_occurrences = true,
+        parse(try_from_str = parse_shift)
     )]
-    informat: Option<InputFormat>,
+    shift: Vec<(Option<String>, Duration)>,
+
+    /// Correct for an input clock that runs fast by PPM parts per million, or slow if negative.
+    /// The timestamps are corrected relative to the first one of each input. With FILE=, only
+    /// input FILE is corrected. Can be given for several inputs.
+    #[clap(
+        long,
+        value_name = "[FILE=]PPM",
+        allow_hyphen_values = true,
+        multiple_occurrences = true,
+        parse(try_from_str = parse_drift)
+    )]
+    drift: Vec<(Option<String>, f64)>,
+
+    /// Correct for clock drift using two anchors, each telling that the input time OBSERVED was
+    /// actually ACTUAL, e.g. 2022-03-20T10:00:00=2022-03-20T10:00:02.5. Times are in ISO 8601,
+    /// in --intz unless they have an UTC offset. Give this option twice, with FILE= to correct
+    /// only input FILE. Drift or anchors given for an input override those given for all.
+    #[clap(
+        long,
+        value_name = "[FILE=]OBSERVED=ACTUAL",
+        multiple_occurrences = true
+    )]
+    anchor: Vec<String>,
+
+    /// Measure elapsed time from TIME instead of the first timestamp. Lines before it get negative
+    /// elapsed times. TIME is in ISO 8601, in --intz unless it has an UTC offset. Requires
+    /// --outformat elapsed.
+    #[clap(long, value_name = "TIME")]
+    zero: Option<String>,
+
+    /// Measure elapsed time from the first line matching REGEX, or from the next line with a
+    /// timestamp if it has none. Lines are held back until it is found, and if it is not found,
+    /// elapsed time is measured from the first timestamp. Requires --outformat elapsed.
+    #[clap(long, value_name = "REGEX", conflicts_with = "zero")]
+    zero_line: Option<Regex>,
+
+    /// Only output lines timestamped at or after TIME. TIME is in any input format, or a negative
+    /// duration relative to the last timestamp of the input, e.g. -10m. Lines without a timestamp
+    /// go with the preceding line that has one. To find the last timestamp, files are read twice,
+    /// and the whole input is held in memory if it is read from a pipe or standard input.
+    #[clap(long, value_name = "TIME", allow_hyphen_values = true)]
+    since: Option<String>,
+
+    /// Only output lines timestamped at or before TIME, given like for --since.
+    #[clap(long, value_name = "TIME", allow_hyphen_values = true)]
+    until: Option<String>,
+
+    /// Locate the timestamp with a regular expression. The timestamp is the text matched by the
+    /// first capture group, or by the whole expression if it has no groups.
+    #[clap(long, value_name = "REGEX", conflicts_with = "field")]
+    regex: Option<Regex>,
+
+    /// Locate the timestamp in the given field, counting from 1. Fields are separated by
+    /// whitespace, unless a delimiter is given.
+    #[clap(long, value_name = "N", parse(try_from_str = parse_field_index))]
+    field: Option<usize>,
+
+    /// Field delimiter for --field.
+    #[clap(long, value_name = "CHAR", requires = "field")]
+    delimiter: Option<char>,
+
+    /// What to do with lines whose timestamp cannot be output, e.g. due to a too large delta: skip
+    /// (leave the line out), pass (output it as-is) or abort (exit with an error telling the line
+    /// number).
+    #[clap(long, value_name = "POLICY", default_value = "pass")]
+    on_error: ErrorPolicy,
+
+    /// Keep the input format and the previous and first timestamps from file to file, as if the
+    /// files were concatenated. By default each file is processed separately.
+    #[clap(long)]
+    continuous: bool,
+
+    /// Merge the files into one stream ordered by timestamp. Lines without a timestamp stay after
+    /// the preceding line that has one. Deltas and elapsed times are computed over the merged
+    /// stream.
+    #[clap(long, conflicts_with = "continuous")]
+    merge: bool,
+
+    /// Prefix each output line with the name of its file.
+    #[clap(short = 'H', long)]
+    with_filename: bool,
 
     /// Output format.
     #[clap(short,
@@ -54,17 +203,187 @@ struct Args {
         parse(try_from_str=parse_output_format),
     )]
     outformat: OutputFormat,
+
+    /// Files to read. With no files, or when a file is -, read stdin.
+    #[clap(value_name = "FILE")]
+    files: Vec<String>,
+}
+
+/// What to do with a line that cannot be processed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ErrorPolicy {
+    Skip,
+    Pass,
+    Abort,
+}
+
+impl FromStr for ErrorPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ErrorPolicy, String> {
+        match s {
+            "skip" => Ok(ErrorPolicy::Skip),
+            "pass" => Ok(ErrorPolicy::Pass),
+            "abort" => Ok(ErrorPolicy::Abort),
+            _ => Err(format!("Invalid error policy {:?}", s)),
+        }
+    }
+}
+
+/// Error that stops processing.
+#[derive(Debug)]
+enum ProcessError {
+    /// Reading input failed.
+    Read(io::Error),
+    /// Writing output failed.
+    Write(io::Error),
+    /// A line could not be processed and the policy is to abort. Lines are counted from 1.
+    Line(usize, String),
+}
+
+impl fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProcessError::Read(e) | ProcessError::Write(e) => write!(f, "{}", e),
+            ProcessError::Line(n, e) => write!(f, "line {}: {}", n, e),
+        }
+    }
 }
 
-fn parse_input_format(s: &str) -> Result<InputFormat, String> {
+fn parse_strftime(fmt: &str) -> Result<String, String> {
+    if fmt.is_empty() || StrftimeItems::new(fmt).any(|item| item == Item::Error) {
+        return Err(format!("Invalid custom format {:?}", fmt));
+    }
+    Ok(fmt.to_string())
+}
+
+/// Parses ISO 8601 time given as an argument. Time without an UTC offset is in the time zone
+/// given in options.
+fn parse_time_in(s: &str, opts: &ParseOptions) -> Result<NaiveDateTime, String> {
+    khronos::parse_string(s, &InputFormat::Iso8601, opts)
+        .ok_or_else(|| format!("Invalid time {:?}", s))
+}
+
+fn parse_duration_arg(s: &str) -> Result<Duration, String> {
+    khronos::parse_duration(s).ok_or_else(|| format!("Invalid duration {:?}", s))
+}
+
+/// Splits an argument given for one input as FILE=VALUE, where VALUE has no "=", or for all
+/// inputs as VALUE.
+fn split_input_arg(s: &str) -> (Option<String>, &str) {
+    match s.rsplit_once('=') {
+        Some((file, value)) => (Some(file.to_string()), value),
+        None => (None, s),
+    }
+}
+
+fn parse_shift(s: &str) -> Result<(Option<String>, Duration), String> {
+    let (file, value) = split_input_arg(s);
+    Ok((file, parse_duration_arg(value)?))
+}
+
+/// Parses drift rate in ppm. A clock running slow by a million ppm or more would stand still or
+/// run backwards.
+fn parse_drift(s: &str) -> Result<(Option<String>, f64), String> {
+    let (file, value) = split_input_arg(s);
+    match value.parse::<f64>() {
+        Ok(ppm) if ppm.is_finite() && ppm > -1e6 => Ok((file, ppm)),
+        _ => Err(format!(
+            "Invalid drift {:?}: must be a number over -1000000",
+            value
+        )),
+    }
+}
+
+/// Parses an anchor of drift correction. Times without an UTC offset are in the time zone given
+/// in options.
+fn parse_anchor(
+    s: &str,
+    opts: &ParseOptions,
+) -> Result<(Option<String>, (NaiveDateTime, NaiveDateTime)), String> {
+    let (rest, actual) = s
+        .rsplit_once('=')
+        .ok_or_else(|| format!("Invalid anchor {:?}", s))?;
+    let (file, observed) = split_input_arg(rest);
+    Ok((
+        file,
+        (parse_time_in(observed, opts)?, parse_time_in(actual, opts)?),
+    ))
+}
+
+/// Returns the last value of an option given for input `file`, or for all inputs if None.
+fn given_for<T: Copy>(values: &[(Option<String>, T)], file: Option<&str>) -> Option<T> {
+    (values.iter().rev())
+        .find(|(f, _)| f.as_deref() == file)
+        .map(|(_, value)| *value)
+}
+
+/// Returns the last value of an option given for input `path`, or if none, the last one given
+/// for all inputs.
+fn for_input<T: Copy>(values: &[(Option<String>, T)], path: &str) -> Option<T> {
+    given_for(values, Some(path)).or_else(|| given_for(values, None))
+}
+
+/// Parses custom input format. Timestamps are parsed to NaiveDateTime, so the format must give at
+/// least the date, hour and minute: a time formatted with it must parse back.
+fn parse_custom_informat(fmt: &str) -> Result<InputFormat, String> {
+    use std::fmt::Write as _;
+
+    let fmt = parse_strftime(fmt)?;
+    let t = NaiveDate::from_ymd_opt(2001, 2, 3)
+        .and_then(|date| date.and_hms_milli_opt(4, 5, 6, 789))
+        .unwrap();
+    let mut s = String::new();
+    let t = DateTime::<Utc>::from_naive_utc_and_offset(t, Utc);
+    if write!(s, "{}", t.format(&fmt)).is_err() || NaiveDateTime::parse_from_str(&s, &fmt).is_err()
+    {
+        return Err(format!("Custom format {:?} lacks date or time", fmt));
+    }
+    Ok(InputFormat::Custom(fmt))
+}
+
+/// Parses input format. The times of since: and dmesg: are in the time zone given in options
+/// unless they have an UTC offset.
+fn parse_input_format(s: &str, opts: &ParseOptions) -> Result<InputFormat, String> {
+    if let Some(fmt) = s.strip_prefix("custom:") {
+        return parse_custom_informat(fmt);
+    }
+    if let Some(epoc) = s.strip_prefix("since:") {
+        return parse_time_in(epoc, opts).map(InputFormat::Epoc);
+    }
+    if s == "dmesg" {
+        return khronos::boot_time()
+            .map(InputFormat::Epoc)
+            .map_err(|e| format!("Cannot read boot time: {}", e));
+    }
+    if let Some(boot) = s.strip_prefix("dmesg:") {
+        let boot = match boot.split_once('@') {
+            Some((uptime, t)) => khronos::boot_time_from_anchor(uptime, parse_time_in(t, opts)?)
+                .ok_or_else(|| format!("Invalid time since boot {:?}", uptime))?,
+            None => parse_time_in(boot, opts)?,
+        };
+        return Ok(InputFormat::Epoc(boot));
+    }
     match s {
         "unix" => Ok(InputFormat::Unix),
         "unixms" => Ok(InputFormat::UnixMs),
+        "unixus" => Ok(InputFormat::UnixUs),
+        "unixns" => Ok(InputFormat::UnixNs),
         "iso" => Ok(InputFormat::Iso8601),
+        "syslog" => Ok(InputFormat::Syslog),
+        "rfc5424" => Ok(InputFormat::Rfc5424),
+        "clf" => Ok(InputFormat::Clf),
         _ => Err("Invalid format".to_string()),
     }
 }
 
+fn parse_field_index(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(0) | Err(_) => Err("Field index must be a positive integer".to_string()),
+        Ok(i) => Ok(i),
+    }
+}
+
 fn try_parse_unit(s: &str) -> Option<Unit> {
     match s {
         "s" => Some(Unit::Seconds),
@@ -76,34 +395,71 @@ fn try_parse_unit(s: &str) -> Option<Unit> {
 }
 
 fn try_parse_precision(s: &str) -> Option<Precision> {
-    if s.starts_with(".") {
-        s[1..]
-            .parse()
-            .ok()
-            .filter(|x| *x <= 9)
-            .map(|x| Precision(x))
-    } else {
-        None
+    s.strip_prefix('.')?
+        .parse()
+        .ok()
+        .filter(|x| *x <= 9)
+        .map(Precision)
+}
+
+fn try_parse_zone(s: &str) -> Result<Option<Zone>, String> {
+    match s {
+        "utc" | "local" => s.parse().map(Some),
+        _ => match s.strip_prefix("tz=") {
+            Some(tz) => tz.parse().map(Some),
+            None => Ok(None),
+        },
     }
 }
 
 fn parse_output_format(s: &str) -> Result<OutputFormat, String> {
+    // The strftime format may contain commas, so options go before it.
+    if s.starts_with("fmt:") || s.starts_with("fmt,") {
+        let (args, fmt) = s.split_once(':').ok_or_else(|| {
+            "fmt needs a strftime format after a colon, e.g. fmt,local:%H:%M".to_string()
+        })?;
+        let mut zone = Zone::Utc;
+        for a in args.split(',').skip(1) {
+            match try_parse_zone(a)? {
+                Some(z) => zone = z,
+                None => return Err(format!("Invalid format argument {:?}", a)),
+            }
+        }
+        let fmt = parse_strftime(fmt)?;
+        return Ok(OutputFormat::Custom { fmt, zone });
+    }
+
     let args = s.split(',').collect::<Vec<&str>>();
     let (fmt, args) = args.split_first().unwrap();
     match *fmt {
         "iso" => {
             let mut prec = Precision(0);
             let mut time_only = false;
+            let mut zone = Zone::Utc;
+            let mut offset = false;
+            let mut comma = false;
             for a in args {
                 if let Some(p) = try_parse_precision(a) {
                     prec = p;
                 } else if *a == "nodate" {
                     time_only = true;
+                } else if *a == "offset" {
+                    offset = true;
+                } else if *a == "comma" {
+                    comma = true;
+                } else if let Some(z) = try_parse_zone(a)? {
+                    zone = z;
                 } else {
                     return Err(format!("Invalid format argument {:?}", a));
                 }
             }
-            Ok(OutputFormat::Iso8601 { prec, time_only })
+            Ok(OutputFormat::Iso8601 {
+                prec,
+                time_only,
+                zone,
+                offset,
+                comma,
+            })
         }
         "unix" => {
             let mut unit = Unit::Seconds;
@@ -122,16 +478,29 @@ fn parse_output_format(s: &str) -> Result<OutputFormat, String> {
         "delta" => {
             let mut unit = Unit::Seconds;
             let mut prec = Precision(0);
+            let mut mark = false;
             for a in args {
                 if let Some(u) = try_parse_unit(a) {
                     unit = u;
                 } else if let Some(p) = try_parse_precision(a) {
                     prec = p;
+                } else if *a == "mark" {
+                    mark = true;
                 } else {
                     return Err(format!("Invalid format argument {:?}", a));
                 }
             }
-            Ok(OutputFormat::Delta(unit, prec))
+            Ok(OutputFormat::Delta { unit, prec, mark })
+        }
+        "rfc5424" => {
+            let mut zone = Zone::Utc;
+            for a in args {
+                match try_parse_zone(a)? {
+                    Some(z) => zone = z,
+                    None => return Err(format!("Invalid format argument {:?}", a)),
+                }
+            }
+            Ok(OutputFormat::Rfc5424 { zone })
         }
         "elapsed" => {
             let mut unit = Unit::Seconds;
@@ -151,71 +520,1118 @@ fn parse_output_format(s: &str) -> Result<OutputFormat, String> {
     }
 }
 
+/// Returns the longest prefix of `bytes` that is valid UTF-8.
+fn utf8_prefix(bytes: &[u8]) -> &str {
+    match str::from_utf8(bytes) {
+        Ok(s) => s,
+        // The prefix is valid, so this doesn't fail.
+        Err(e) => str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or_default(),
+    }
+}
+
+/// Reads a line, including its terminator, into `buf`. Returns false at the end of input.
+fn read_line<R: BufRead>(input: &mut R, buf: &mut Vec<u8>) -> Result<bool, ProcessError> {
+    buf.clear();
+    Ok(input.read_until(b'\n', buf).map_err(ProcessError::Read)? > 0)
+}
+
+/// Returns the line without its terminator ("\n" or "\r\n").
+fn strip_terminator(buf: &[u8]) -> &[u8] {
+    let bytes = buf.strip_suffix(b"\n").unwrap_or(buf);
+    bytes.strip_suffix(b"\r").unwrap_or(bytes)
+}
+
+/// Timestamp of a line and its position: the line is split into prefix `..start`, timestamp
+/// `start..end` and text `end..`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Stamp {
+    time: NaiveDateTime,
+    start: usize,
+    end: usize,
+    /// Drift correction or shift of the time overflowed, and `time` is as parsed.
+    overflow: bool,
+}
+
+/// Bound of the time window of lines to output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Bound {
+    Time(NaiveDateTime),
+    /// Time relative to the last timestamp of the input.
+    FromEnd(Duration),
+}
+
+impl Bound {
+    /// Parses a bound given as an argument: a negative duration, or a timestamp in the input
+    /// format, or in any format if that fails.
+    fn parse(s: &str, informat: Option<&InputFormat>, opts: &ParseOptions) -> Option<Bound> {
+        match khronos::parse_duration(s) {
+            Some(d) if s.starts_with('-') => Some(Bound::FromEnd(d)),
+            _ => khronos::parse_timestamp(s, informat, opts).map(Bound::Time),
+        }
+    }
+
+    /// Returns the time of the bound, given the last timestamp of the input.
+    fn time(self, last: Option<NaiveDateTime>) -> Option<NaiveDateTime> {
+        match self {
+            Bound::Time(t) => Some(t),
+            Bound::FromEnd(d) => last?.checked_add_signed(d),
+        }
+    }
+}
+
+/// Parsing state of an input, carried from line to line, and from file to file with
+/// --continuous.
+#[derive(Clone, Debug)]
+struct InputState {
+    /// Input format, None until detected.
+    informat: Option<InputFormat>,
+    parse_opts: ParseOptions,
+    /// Timestamp of the previous line that had one, as parsed.
+    last_time: Option<NaiveDateTime>,
+    /// Drift rate of the input clock in ppm, for which the timestamps are corrected relative to
+    /// the first one.
+    drift: Option<f64>,
+    /// Drift correction, made from `drift` at the first timestamp unless given.
+    correction: Option<DriftCorrection>,
+    /// Added to every timestamp, after drift correction.
+    shift: Duration,
+}
+
+impl InputState {
+    fn new(informat: Option<InputFormat>, parse_opts: ParseOptions) -> InputState {
+        InputState {
+            informat,
+            parse_opts,
+            last_time: None,
+            drift: None,
+            correction: None,
+            shift: Duration::zero(),
+        }
+    }
+
+    /// Finds and parses the timestamp of a line, given without its terminator.
+    ///
+    /// Lines need not be valid UTF-8. The timestamp is looked for in the valid part in the
+    /// beginning of the line.
+    fn parse(&mut self, bytes: &[u8]) -> Option<Stamp> {
+        let line = utf8_prefix(bytes);
+
+        // Try to auto-detect input format if it's not known.
+        if self.informat.is_none() {
+            self.informat = khronos::detect_format(line, &self.parse_opts);
+            // The detected format may be located elsewhere, e.g. Common Log Format.
+            if let (Some(fmt), Locator::Start) = (&self.informat, &self.parse_opts.locator) {
+                self.parse_opts.locator = fmt.default_locator();
+            }
+        }
+        let fmt = self.informat.as_ref()?;
+        let (mut prefix, mut time, mut text) = khronos::parse_line(line, fmt, &self.parse_opts);
+
+        // Timestamps without a year are assumed to be in order, so jumping back more than half a
+        // year means that the year has changed.
+        if let (InputFormat::Syslog, Some(t), Some(prev)) = (fmt, time, self.last_time) {
+            if t < prev - Duration::days(183) {
+                self.parse_opts.year += 1;
+                (prefix, time, text) = khronos::parse_line(line, fmt, &self.parse_opts);
+            }
+        }
+
+        let time = time?;
+        self.last_time = Some(time);
+        if let (None, Some(ppm)) = (self.correction, self.drift) {
+            self.correction = Some(DriftCorrection::from_drift(time, ppm));
+        }
+        let corrected = match self.correction {
+            Some(correction) => correction.apply(time),
+            None => Some(time),
+        }
+        .and_then(|t| t.checked_add_signed(self.shift));
+        Some(Stamp {
+            time: corrected.unwrap_or(time),
+            start: prefix.len(),
+            end: line.len() - text.len(),
+            overflow: corrected.is_none(),
+        })
+    }
+}
+
+/// Output format and the timestamps of the previous and the first output line that had one.
+#[derive(Clone, Debug)]
+struct Output {
+    format: OutputFormat,
+    policy: ErrorPolicy,
+    prev_time: Option<NaiveDateTime>,
+    /// Zero point of elapsed time: the first timestamp unless given.
+    first_time: Option<NaiveDateTime>,
+    /// If given, the zero point of elapsed time is the timestamp of the first line matching this,
+    /// or of the next line with a timestamp if that has none.
+    zero_line: Option<Regex>,
+    /// Whether a line matching `zero_line` has been seen.
+    zero_matched: bool,
+    /// Lines held back until the zero point is found, with the index of their input.
+    held: Vec<(usize, Line)>,
+    /// Bounds of the time window of lines to output.
+    since: Option<Bound>,
+    until: Option<Bound>,
+    /// Whether the previous line with a timestamp was in the time window, None before the first
+    /// one.
+    included: Option<bool>,
+    /// Timestamp of the previous line that had one, whether output or not.
+    last_time: Option<NaiveDateTime>,
+}
+
+impl Output {
+    fn new(format: OutputFormat, policy: ErrorPolicy) -> Output {
+        Output {
+            format,
+            policy,
+            prev_time: None,
+            first_time: None,
+            zero_line: None,
+            zero_matched: false,
+            held: vec![],
+            since: None,
+            until: None,
+            included: None,
+            last_time: None,
+        }
+    }
+
+    /// Rewrites the timestamp of a line from input `source` and passes the line to `func` as
+    /// `source`, prefix, rewritten timestamp and the rest of the line, if it is in the time
+    /// window. Lines are held back until it is known whether they are in the window and, with
+    /// `zero_line`, until the zero point of elapsed time is found, or `finish` is called.
+    fn write<F>(
+        &mut self,
+        source: usize,
+        line: Line,
+        func: &mut F,
+    ) -> Result<(), (usize, ProcessError)>
+    where
+        F: FnMut(usize, &str, &str, &[u8]) -> io::Result<()>,
+    {
+        if let Some(stamp) = line.stamp {
+            self.last_time = Some(stamp.time);
+        }
+        if let Some(re) = &self.zero_line {
+            self.zero_matched |= re.is_match(&String::from_utf8_lossy(strip_terminator(&line.buf)));
+            if let (true, Some(stamp)) = (self.zero_matched, line.stamp) {
+                self.first_time = Some(stamp.time);
+                self.zero_line = None;
+            }
+        }
+        self.held.push((source, line));
+
+        // Relative bounds are known only at the end. Lines before the first timestamp go with the
+        // first line that has one.
+        let relative = [self.since, self.until]
+            .iter()
+            .any(|bound| matches!(bound, Some(Bound::FromEnd(_))));
+        let filtered = self.since.is_some() || self.until.is_some();
+        if self.zero_line.is_none() && !relative && (!filtered || self.last_time.is_some()) {
+            self.flush(func)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the lines held back. If the zero point of elapsed time was not found, elapsed time
+    /// is measured from the first timestamp.
+    fn finish<F>(&mut self, func: &mut F) -> Result<(), (usize, ProcessError)>
+    where
+        F: FnMut(usize, &str, &str, &[u8]) -> io::Result<()>,
+    {
+        self.zero_line = None;
+        self.flush(func)
+    }
+
+    /// Writes the lines held back that are in the time window.
+    fn flush<F>(&mut self, func: &mut F) -> Result<(), (usize, ProcessError)>
+    where
+        F: FnMut(usize, &str, &str, &[u8]) -> io::Result<()>,
+    {
+        let held = mem::take(&mut self.held);
+        if self.included.is_none() {
+            self.included = held
+                .iter()
+                .find_map(|(_, line)| line.stamp)
+                .map(|stamp| self.in_window(stamp.time));
+        }
+        for (source, line) in held {
+            if let Some(stamp) = line.stamp {
+                self.included = Some(self.in_window(stamp.time));
+            }
+            // Lines are output if there are no timestamps at all.
+            if self.included != Some(false) {
+                self.write_line(source, &line, func)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes bounds relative to the last timestamp absolute, given that timestamp, so that lines
+    /// need not be held back until the end of the input.
+    fn resolve_bounds(&mut self, last: Option<NaiveDateTime>) {
+        self.since = self
+            .since
+            .and_then(|bound| bound.time(last))
+            .map(Bound::Time);
+        self.until = self
+            .until
+            .and_then(|bound| bound.time(last))
+            .map(Bound::Time);
+    }
+
+    fn in_window(&self, t: NaiveDateTime) -> bool {
+        let since = self.since.and_then(|bound| bound.time(self.last_time));
+        let until = self.until.and_then(|bound| bound.time(self.last_time));
+        since.is_none_or(|since| t >= since) && until.is_none_or(|until| t <= until)
+    }
+
+    /// Writes a line without holding it back. If the timestamp cannot be written, the line is
+    /// handled according to the error policy. Lines without a timestamp are passed as-is.
+    fn write_line<F>(
+        &mut self,
+        source: usize,
+        line: &Line,
+        func: &mut F,
+    ) -> Result<(), (usize, ProcessError)>
+    where
+        F: FnMut(usize, &str, &str, &[u8]) -> io::Result<()>,
+    {
+        let buf = &line.buf;
+        let stamp = match line.stamp {
+            Some(stamp) => stamp,
+            None => return func(source, "", "", buf).map_err(|e| (source, ProcessError::Write(e))),
+        };
+        // Only lines whose timestamp is written count as the previous and the first one.
+        let s = if stamp.overflow {
+            Err("Corrected time out of range".to_string())
+        } else {
+            khronos::write(&self.format, stamp.time, self.prev_time, self.first_time)
+                .map_err(|e| e.to_string())
+        };
+        match (s, self.policy) {
+            (Ok(s), _) => {
+                self.prev_time = Some(stamp.time);
+                self.first_time = self.first_time.or(Some(stamp.time));
+                func(
+                    source,
+                    utf8_prefix(&buf[..stamp.start]),
+                    &s,
+                    &buf[stamp.end..],
+                )
+            }
+            (Err(_), ErrorPolicy::Skip) => Ok(()),
+            (Err(_), ErrorPolicy::Pass) => func(source, "", "", buf),
+            (Err(e), ErrorPolicy::Abort) => {
+                return Err((source, ProcessError::Line(line.number, e)))
+            }
+        }
+        .map_err(|e| (source, ProcessError::Write(e)))
+    }
+}
+
+/// Line of input with its timestamp. Lines are counted from 1.
+#[derive(Clone, Debug)]
+struct Line {
+    number: usize,
+    buf: Vec<u8>,
+    stamp: Option<Stamp>,
+}
+
+/// Reads lines from input `source`, rewrites their timestamps and passes each line to `func` as
+/// `source`, prefix, rewritten timestamp and the rest of the line, including the original line
+/// terminator ("\n", "\r\n" or none at the end of input). Lines held back by `output` are passed
+/// on when it is finished. Errors come with `source`.
 fn process_text<R, F>(
-    mut informat: Option<InputFormat>,
-    outformat: OutputFormat,
-    input: R,
+    state: &mut InputState,
+    output: &mut Output,
+    source: usize,
+    mut input: R,
     mut func: F,
-) where
+) -> Result<(), (usize, ProcessError)>
+where
     R: BufRead,
-    F: FnMut(&str, &str),
+    F: FnMut(usize, &str, &str, &[u8]) -> io::Result<()>,
 {
-    let mut prev_time = None;
-    let mut first_time = None;
-    for line in input.lines().map(|x| x.expect("line error")) {
-        // Try to auto-detect input format if it's not known.
-        if informat.is_none() {
-            informat = khronos::detect_format(&line);
-        }
-
-        // Process line.
-        if let Some(ref fmt) = informat {
-            let (time, text) = khronos::parse_line(&line, fmt);
-            let outtime = match time {
-                Some(t) => {
-                    let s = khronos::write(outformat, t, prev_time, first_time);
-                    prev_time = time;
-                    first_time = first_time.or(time);
-                    s
+    let mut buf = Vec::new();
+    for number in 1.. {
+        if !read_line(&mut input, &mut buf).map_err(|e| (source, e))? {
+            break;
+        }
+        let line = Line {
+            number,
+            stamp: state.parse(strip_terminator(&buf)),
+            buf: mem::take(&mut buf),
+        };
+        output.write(source, line, &mut func)?;
+    }
+    Ok(())
+}
+
+/// Input to be merged, read one record at a time. A record is a line with a timestamp followed
+/// by the lines without one, e.g. the continuation lines of a multi-line message. Lines without a
+/// timestamp in the beginning of the input make up a record of their own.
+struct MergeInput<R> {
+    input: R,
+    state: InputState,
+    number: usize,
+    /// First line of the next record, if already read.
+    next: Option<Line>,
+    /// Error that ended reading the input.
+    error: Option<ProcessError>,
+}
+
+impl<R: BufRead> MergeInput<R> {
+    fn new(input: R, state: InputState) -> MergeInput<R> {
+        MergeInput {
+            input,
+            state,
+            number: 0,
+            next: None,
+            error: None,
+        }
+    }
+
+    /// Reads the next record. Returns an empty record at the end of input, or after a read error,
+    /// which is kept in `error`.
+    fn read_record(&mut self) -> Vec<Line> {
+        let mut record: Vec<Line> = self.next.take().into_iter().collect();
+        let mut buf = Vec::new();
+        while self.error.is_none() {
+            match read_line(&mut self.input, &mut buf) {
+                Ok(true) => (),
+                Ok(false) => break,
+                Err(e) => {
+                    self.error = Some(e);
+                    break;
                 }
-                None => "".to_string(),
+            }
+            self.number += 1;
+            let line = Line {
+                number: self.number,
+                stamp: self.state.parse(strip_terminator(&buf)),
+                buf: mem::take(&mut buf),
             };
-            func(&outtime, text);
-        } else {
-            func("", &line);
+            if line.stamp.is_some() && !record.is_empty() {
+                self.next = Some(line);
+                break;
+            }
+            record.push(line);
+        }
+        record
+    }
+}
+
+/// Merges the inputs into one stream ordered by timestamp. Each input is assumed to be in order.
+/// The lines are passed to `func` like in `process_text`, along with the index of their input.
+///
+/// Records with equal timestamps are taken from the inputs in order. Lines missing a terminator
+/// get "\n", so that they are not joined with lines from other inputs. Lines held back by
+/// `output` are passed on when it is finished. An input whose reading fails ends there, and the
+/// others are merged on; the error is left in the input.
+fn merge_text<R, F>(
+    inputs: &mut [MergeInput<R>],
+    output: &mut Output,
+    mut func: F,
+) -> Result<(), (usize, ProcessError)>
+where
+    R: BufRead,
+    F: FnMut(usize, &str, &str, &[u8]) -> io::Result<()>,
+{
+    let mut records: Vec<Vec<Line>> = inputs.iter_mut().map(MergeInput::read_record).collect();
+    // Records without a timestamp, i.e. lines in the beginning of an input, go first.
+    while let Some(i) = (0..records.len())
+        .filter(|&i| !records[i].is_empty())
+        .min_by_key(|&i| records[i][0].stamp.map(|stamp| stamp.time))
+    {
+        let next = inputs[i].read_record();
+        for mut line in mem::replace(&mut records[i], next) {
+            if !line.buf.ends_with(b"\n") {
+                line.buf.push(b'\n');
+            }
+            output.write(i, line, &mut func)?;
+        }
+    }
+    Ok(())
+}
+
+/// Opens a file for reading, or stdin if the path is "-".
+fn open(path: &str) -> io::Result<Box<dyn BufRead>> {
+    Ok(match path {
+        "-" => Box::new(io::stdin().lock()),
+        _ => Box::new(io::BufReader::new(File::open(path)?)),
+    })
+}
+
+/// Returns the modification time of a file, or of stdin if it is redirected from a file. Stdin is
+/// looked up through /dev/stdin, so elsewhere than on Unix its modification time is not known.
+fn modified(path: &str) -> Option<NaiveDateTime> {
+    let path = match path {
+        "-" if cfg!(unix) => "/dev/stdin",
+        "-" => return None,
+        _ => path,
+    };
+    let metadata = fs::metadata(path).ok()?;
+    if !metadata.is_file() {
+        return None;
+    }
+    Some(DateTime::<Utc>::from(metadata.modified().ok()?).naive_utc())
+}
+
+/// Returns whether an input can be read twice.
+fn is_file(path: &str) -> bool {
+    path != "-" && fs::metadata(path).is_ok_and(|metadata| metadata.is_file())
+}
+
+/// Reads inputs ahead, in sequence with one state or merged, and returns their last timestamp.
+/// Errors are ignored, as they are reported when the inputs are processed.
+fn read_ahead<F>(paths: &[String], merge: bool, new_state: F) -> Option<NaiveDateTime>
+where
+    F: Fn(&str) -> InputState,
+{
+    let mut output = Output::new(
+        OutputFormat::Unix(Unit::Seconds, Precision(0)),
+        ErrorPolicy::Skip,
+    );
+    let mut func = |_: usize, _: &str, _: &str, _: &[u8]| Ok(());
+    if merge {
+        let mut inputs: Vec<_> = (paths.iter())
+            .filter_map(|path| Some(MergeInput::new(open(path).ok()?, new_state(path))))
+            .collect();
+        let _ = merge_text(&mut inputs, &mut output, &mut func);
+    } else {
+        let mut state = new_state(&paths[paths.len() - 1]);
+        for input in paths.iter().filter_map(|path| open(path).ok()) {
+            let _ = process_text(&mut state, &mut output, 0, input, &mut func);
+        }
+    }
+    output.last_time
+}
+
+/// Reports an error from processing the inputs with the given names. Returns true after a read
+/// error, which doesn't stop processing the other inputs, and exits on other errors.
+fn report(result: Result<(), (usize, ProcessError)>, names: &[&str]) -> bool {
+    match result {
+        Ok(()) => false,
+        Err((i, ProcessError::Read(e))) => {
+            eprintln!("khronos: {}: {}", names[i], e);
+            true
+        }
+        // Output closed, e.g. by `head`.
+        Err((_, ProcessError::Write(e))) if e.kind() == io::ErrorKind::BrokenPipe => {
+            process::exit(0)
+        }
+        Err((i, e)) => {
+            eprintln!("khronos: {}: {}", names[i], e);
+            process::exit(1);
         }
     }
 }
 
+/// Exits with a usage error for an option value that is parsed after the arguments.
+fn invalid_value(arg: &str, value: &str, e: String) -> ! {
+    Args::command()
+        .error(
+            ErrorKind::InvalidValue,
+            format!("Invalid value {:?} for '{}': {}", value, arg, e),
+        )
+        .exit()
+}
+
 fn main() {
     let args = Args::parse();
 
-    process_text(
-        args.informat,
-        args.outformat,
-        io::stdin().lock(),
-        |time, text| println!("{}{}", time, text),
-    );
-}
+    // Times given as arguments are in --intz unless they have an UTC offset. Times without a year
+    // are assumed to be in the past.
+    let now = Utc::now().naive_utc();
+    let arg_opts = ParseOptions {
+        zone: args.intz,
+        dst: args.dst,
+        locator: Locator::Start,
+        year: args.year.unwrap_or_else(|| now.year()),
+        latest: args.year.is_none().then_some(now),
+    };
+    let informat = args.informat.as_ref().map(|s| {
+        parse_input_format(s, &arg_opts).unwrap_or_else(|e| invalid_value("--informat <FMT>", s, e))
+    });
+    let anchors: Vec<_> = (args.anchor.iter())
+        .map(|s| {
+            parse_anchor(s, &arg_opts)
+                .unwrap_or_else(|e| invalid_value("--anchor <[FILE=]OBSERVED=ACTUAL>", s, e))
+        })
+        .collect();
+    let zero = args.zero.as_ref().map(|s| {
+        parse_time_in(s, &arg_opts).unwrap_or_else(|e| invalid_value("--zero <TIME>", s, e))
+    });
+
+    // Only elapsed time has a zero point, and --zero-line would hold back output for nothing.
+    if !matches!(args.outformat, OutputFormat::Elapsed(..)) {
+        let option = match (&args.zero, &args.zero_line) {
+            (Some(_), _) => Some("--zero"),
+            (_, Some(_)) => Some("--zero-line"),
+            _ => None,
+        };
+        if let Some(option) = option {
+            Args::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!("{} requires --outformat elapsed", option),
+                )
+                .exit()
+        }
+    }
+
+    let locator = match (args.regex, args.field) {
+        (Some(re), _) => Locator::Regex(re),
+        (None, Some(index)) => Locator::Field {
+            index,
+            delimiter: args.delimiter,
+        },
+        (None, None) => informat
+            .as_ref()
+            .map_or(Locator::Start, InputFormat::default_locator),
+    };
+    let parse_bound = |s: &Option<String>| {
+        let s = s.as_ref()?;
+        match Bound::parse(s, informat.as_ref(), &arg_opts) {
+            Some(bound) => Some(bound),
+            None => Args::command()
+                .error(ErrorKind::ValueValidation, format!("Invalid time {:?}", s))
+                .exit(),
+        }
+    };
+    let (since, until) = (parse_bound(&args.since), parse_bound(&args.until));
+
+    let paths = match args.files.is_empty() {
+        true => vec!["-".to_string()],
+        false => args.files.clone(),
+    };
+
+    // Shift and drift can be given for all inputs, or for one input with FILE=. In continuous mode
+    // the files make up one log from one clock.
+    let files = (args.shift.iter().map(|(f, _)| (f, "--shift")))
+        .chain(args.drift.iter().map(|(f, _)| (f, "--drift")))
+        .chain(anchors.iter().map(|(f, _)| (f, "--anchor")));
+    for (file, option) in files {
+        let file = match file {
+            Some(file) => file,
+            None => continue,
+        };
+        if args.continuous {
+            Args::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!("{} can't be given for one input with --continuous", option),
+                )
+                .exit()
+        }
+        if !paths.contains(file) {
+            Args::command()
+                .error(
+                    ErrorKind::ValueValidation,
+                    format!("{} is given for {:?}, which is not an input", option, file),
+                )
+                .exit()
+        }
+    }
+    let mut corrections = vec![];
+    for file in std::iter::once(None).chain(paths.iter().map(Some)) {
+        let file_anchors: Vec<_> = (anchors.iter())
+            .filter(|(f, _)| f.as_ref() == file)
+            .map(|(_, anchor)| *anchor)
+            .collect();
+        let file = file.map(String::as_str);
+        if !file_anchors.is_empty() && given_for(&args.drift, file).is_some() {
+            let inputs = file.map_or("all inputs".to_string(), |f| format!("{:?}", f));
+            Args::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    format!("--drift and --anchor are both given for {}", inputs),
+                )
+                .exit()
+        }
+        let correction = match file_anchors[..] {
+            [] => continue,
+            [a, b] => DriftCorrection::from_anchors(a, b),
+            _ => Args::command()
+                .error(
+                    ErrorKind::WrongNumberOfValues,
+                    "--anchor must be given twice",
+                )
+                .exit(),
+        };
+        match correction {
+            Some(correction) => corrections.push((file.map(str::to_string), correction)),
+            None => Args::command()
+                .error(
+                    ErrorKind::ValueValidation,
+                    "The observed times of --anchor must differ",
+                )
+                .exit(),
+        }
+    }
+
+    // Timestamps without a year are assumed to be before the modification time of the input.
+    // In continuous mode the files make up one log, which ends in the last file.
+    let new_state = |path: &str| {
+        let (year, latest) = match args.year {
+            Some(year) => (year, None),
+            None => {
+                let latest = modified(path).unwrap_or_else(|| Utc::now().naive_utc());
+                (latest.year(), Some(latest))
+            }
+        };
+        let parse_opts = ParseOptions {
+            zone: args.intz,
+            dst: args.dst,
+            locator: locator.clone(),
+            year,
+            latest,
+        };
+        let (drift, correction) = match (
+            given_for(&args.drift, Some(path)),
+            given_for(&corrections, Some(path)),
+        ) {
+            (None, None) => (given_for(&args.drift, None), given_for(&corrections, None)),
+            given => given,
+        };
+        InputState {
+            drift,
+            correction,
+            shift: for_input(&args.shift, path).unwrap_or_else(Duration::zero),
+            ..InputState::new(informat.clone(), parse_opts)
+        }
+    };
+    let names: Vec<&str> = paths
+        .iter()
+        .map(|path| match path.as_str() {
+            "-" => "(standard input)",
+            _ => path,
+        })
+        .collect();
+
+    let new_output = || Output {
+        first_time: zero,
+        zero_line: args.zero_line.clone(),
+        since,
+        until,
+        ..Output::new(args.outformat.clone(), args.on_error)
+    };
+
+    let mut out = io::stdout().lock();
+    let mut write = |name: &str, prefix: &str, time: &str, text: &[u8]| {
+        if args.with_filename {
+            out.write_all(name.as_bytes())?;
+            out.write_all(b":")?;
+        }
+        out.write_all(prefix.as_bytes())?;
+        out.write_all(time.as_bytes())?;
+        out.write_all(text)
+    };
+    let mut failed = false;
+
+    // Relative bounds need the last timestamp, which is found by reading files ahead. Other inputs
+    // are held back until their end.
+    let read_twice = [since, until]
+        .iter()
+        .any(|bound| matches!(bound, Some(Bound::FromEnd(_))))
+        && paths.iter().all(|path| is_file(path));
+
+    if args.merge {
+        let mut inputs = vec![];
+        let mut input_names = vec![];
+        for (path, name) in paths.iter().zip(&names) {
+            match open(path) {
+                Ok(input) => {
+                    inputs.push(MergeInput::new(input, new_state(path)));
+                    input_names.push(*name);
+                }
+                Err(e) => {
+                    eprintln!("khronos: {}: {}", name, e);
+                    failed = true;
+                }
+            }
+        }
+        let mut output = new_output();
+        if read_twice {
+            output.resolve_bounds(read_ahead(&paths, true, new_state));
+        }
+        let mut func = |i: usize, prefix: &str, time: &str, text: &[u8]| {
+            write(input_names[i], prefix, time, text)
+        };
+        let result = merge_text(&mut inputs, &mut output, &mut func);
+        for (i, input) in inputs.iter_mut().enumerate() {
+            if let Some(e) = input.error.take() {
+                failed |= report(Err((i, e)), &input_names);
+            }
+        }
+        failed |= report(result, &input_names);
+        failed |= report(output.finish(&mut func), &input_names);
+    } else {
+        let mut state = new_state(paths.last().unwrap());
+        let mut output = new_output();
+        if read_twice && args.continuous {
+            output.resolve_bounds(read_ahead(&paths, false, new_state));
+        }
+        let mut func =
+            |i: usize, prefix: &str, time: &str, text: &[u8]| write(names[i], prefix, time, text);
+        for (i, path) in paths.iter().enumerate() {
+            let input = match open(path) {
+                Ok(input) => input,
+                Err(e) => {
+                    eprintln!("khronos: {}: {}", names[i], e);
+                    failed = true;
+                    continue;
+                }
+            };
+            if !args.continuous {
+                state = new_state(path);
+                if read_twice {
+                    let path = slice::from_ref(path);
+                    output.resolve_bounds(read_ahead(path, false, new_state));
+                }
+            }
+            let result = process_text(&mut state, &mut output, i, input, &mut func);
+            failed |= report(result, &names);
+            if !args.continuous {
+                failed |= report(output.finish(&mut func), &names);
+                output = new_output();
+            }
+        }
+        failed |= report(output.finish(&mut func), &names);
+    }
+
+    if let Err(e) = io::stdout().flush() {
+        if e.kind() != io::ErrorKind::BrokenPipe {
+            eprintln!("khronos: {}", e);
+            process::exit(1);
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveTime;
+
+    /// Parses ISO 8601 time, in UTC unless it has an UTC offset.
+    fn parse_time_arg(s: &str) -> Result<NaiveDateTime, String> {
+        parse_time_in(s, &ParseOptions::default())
+    }
+
+    fn check_process_text(
+        informat: Option<InputFormat>,
+        outformat: OutputFormat,
+        input: &str,
+        expected_output: Vec<(&str, &str, &str)>,
+    ) {
+        check_process_text_with(
+            informat,
+            &ParseOptions::default(),
+            outformat,
+            input,
+            expected_output,
+        )
+    }
+
+    fn check_process_text_with(
+        informat: Option<InputFormat>,
+        parse_opts: &ParseOptions,
+        outformat: OutputFormat,
+        input: &str,
+        expected_output: Vec<(&str, &str, &str)>,
+    ) {
+        check_process_text_with_state(
+            InputState::new(informat, parse_opts.clone()),
+            outformat,
+            input,
+            expected_output,
+        )
+    }
+
+    fn check_process_text_with_state(
+        mut state: InputState,
+        outformat: OutputFormat,
+        input: &str,
+        expected_output: Vec<(&str, &str, &str)>,
+    ) {
+        let cursor = io::Cursor::new(input);
+        let mut expected_iter = expected_output.iter();
+        process_text(
+            &mut state,
+            &mut Output::new(outformat, ErrorPolicy::Abort),
+            0,
+            cursor,
+            |_, prefix, time, text| {
+                // Line feeds are not compared.
+                let text = text.strip_suffix(b"\n").unwrap_or(text);
+                let expected = expected_iter.next().expect("produced too many lines");
+                assert_eq!(
+                    (prefix, time, text),
+                    (expected.0, expected.1, expected.2.as_bytes())
+                );
+                Ok(())
+            },
+        )
+        .unwrap();
+    }
+
+    /// Processes input with given error policy, returning the output lines.
+    fn process_with_policy(
+        input: &[u8],
+        policy: ErrorPolicy,
+    ) -> (Vec<Vec<u8>>, Result<(), ProcessError>) {
+        let mut output = vec![];
+        let result = process_text(
+            &mut InputState::new(Some(InputFormat::Unix), ParseOptions::default()),
+            &mut Output::new(
+                OutputFormat::Delta {
+                    unit: Unit::Seconds,
+                    prec: Precision(0),
+                    mark: false,
+                },
+                policy,
+            ),
+            0,
+            input,
+            |_, prefix, time, text| {
+                output.push([prefix.as_bytes(), time.as_bytes(), text].concat());
+                Ok(())
+            },
+        );
+        (output, result.map_err(|(_, e)| e))
+    }
+
+    /// Processes inputs one after another with the same state and output, returning the output
+    /// lines.
+    fn process_continuous(inputs: &[&str], mut out: Output) -> Vec<String> {
+        let mut state = InputState::new(None, ParseOptions::default());
+        let mut output = vec![];
+        let mut func = |_, prefix: &str, time: &str, text: &[u8]| {
+            output.push(format!(
+                "{}{}{}",
+                prefix,
+                time,
+                str::from_utf8(text).unwrap()
+            ));
+            Ok(())
+        };
+        for (i, input) in inputs.iter().enumerate() {
+            process_text(&mut state, &mut out, i, input.as_bytes(), &mut func).unwrap();
+        }
+        out.finish(&mut func).unwrap();
+        output
+    }
+
+    /// Merges inputs, returning the output lines tagged with the index of their input.
+    fn merge(inputs: &[&str], outformat: OutputFormat) -> Vec<(usize, String)> {
+        let mut inputs: Vec<_> = inputs
+            .iter()
+            .map(|input| {
+                MergeInput::new(
+                    input.as_bytes(),
+                    InputState::new(None, ParseOptions::default()),
+                )
+            })
+            .collect();
+        let mut output = vec![];
+        merge_text(
+            &mut inputs,
+            &mut Output::new(outformat, ErrorPolicy::Abort),
+            |i, prefix, time, text| {
+                let text = str::from_utf8(text).unwrap();
+                output.push((i, format!("{}{}{}", prefix, time, text)));
+                Ok(())
+            },
+        )
+        .unwrap();
+        output
+    }
+
+    /// Reader that fails after the given data.
+    struct FailingReader<'a>(&'a [u8]);
+
+    impl io::Read for FailingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.is_empty() {
+                true => Err(io::Error::other("read failed")),
+                false => self.0.read(buf),
+            }
+        }
+    }
+
+    #[test]
+    fn merge_read_error() {
+        let mut inputs: Vec<MergeInput<Box<dyn BufRead>>> = vec![
+            MergeInput::new(
+                Box::new(io::BufReader::new(FailingReader(b"10 a\n12 b\n"))),
+                InputState::new(None, ParseOptions::default()),
+            ),
+            MergeInput::new(
+                Box::new(&b"11 c\n13 d\n"[..]),
+                InputState::new(None, ParseOptions::default()),
+            ),
+        ];
+        let mut output = vec![];
+        merge_text(
+            &mut inputs,
+            &mut Output::new(
+                OutputFormat::Unix(Unit::Seconds, Precision(0)),
+                ErrorPolicy::Abort,
+            ),
+            |i, prefix, time, text| {
+                let text = str::from_utf8(text).unwrap();
+                output.push((i, format!("{}{}{}", prefix, time, text)));
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            [
+                (0, "10 a\n".to_string()),
+                (1, "11 c\n".to_string()),
+                (0, "12 b\n".to_string()),
+                (1, "13 d\n".to_string())
+            ]
+        );
+        assert!(matches!(inputs[0].error, Some(ProcessError::Read(_))));
+        assert!(inputs[1].error.is_none());
+    }
+
+    #[test]
+    fn merge_inputs() {
+        assert_eq!(
+            merge(
+                &[
+                    "header\n10 a\n13 b\n  continued\n  more\n16 c\n",
+                    "1970-01-01T00:00:11 d\n1970-01-01T00:00:13 e\n  continued",
+                    "",
+                ],
+                OutputFormat::Delta {
+                    unit: Unit::Seconds,
+                    prec: Precision(0),
+                    mark: false,
+                }
+            ),
+            [
+                (0, "header\n".to_string()),
+                (0, "0 a\n".to_string()),
+                (1, "1 d\n".to_string()),
+                (0, "2 b\n".to_string()),
+                (0, "  continued\n".to_string()),
+                (0, "  more\n".to_string()),
+                (1, "0 e\n".to_string()),
+                (1, "  continued\n".to_string()),
+                (0, "3 c\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_leading_untimed_lines() {
+        assert_eq!(
+            merge(
+                &["5 a\n", "x\ny\n1 b\n"],
+                OutputFormat::Unix(Unit::Seconds, Precision(0))
+            ),
+            [
+                (1, "x\n".to_string()),
+                (1, "y\n".to_string()),
+                (1, "1 b\n".to_string()),
+                (0, "5 a\n".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn process_continuous_state() {
+        assert_eq!(
+            process_continuous(
+                &["10 a\n12 b\n", "13 c\n", "x\n15 d\n"],
+                Output::new(
+                    OutputFormat::Delta {
+                        unit: Unit::Seconds,
+                        prec: Precision(0),
+                        mark: false,
+                    },
+                    ErrorPolicy::Abort
+                )
+            ),
+            ["0 a\n", "2 b\n", "1 c\n", "x\n", "2 d\n"]
+        );
+        assert_eq!(
+            process_continuous(
+                &["10 a\n", "1970-01-01T00:00:13 b\n", "14 c\n"],
+                Output::new(
+                    OutputFormat::Elapsed(Unit::Seconds, Precision(0)),
+                    ErrorPolicy::Abort
+                )
+            ),
+            ["0 a\n", "1970-01-01T00:00:13 b\n", "4 c\n"]
+        );
+    }
+
+    #[test]
+    fn process_error_policy() {
+        // Over 292 years between the lines overflows delta. The next delta is from the last line
+        // whose timestamp was written.
+        let input = b"0 a\n10000000000 b\n5 c\n6 d\n";
+        let (output, result) = process_with_policy(input, ErrorPolicy::Skip);
+        assert_eq!(output, [&b"0 a\n"[..], b"5 c\n", b"1 d\n"]);
+        assert!(result.is_ok());
+
+        let (output, result) = process_with_policy(input, ErrorPolicy::Pass);
+        assert_eq!(
+            output,
+            [&b"0 a\n"[..], b"10000000000 b\n", b"5 c\n", b"1 d\n"]
+        );
+        assert!(result.is_ok());
+
+        let (output, result) = process_with_policy(input, ErrorPolicy::Abort);
+        assert_eq!(output, [b"0 a\n"]);
+        assert_eq!(result.unwrap_err().to_string(), "line 2: Too large delta");
+    }
+
+    #[test]
+    fn process_invalid_utf8() {
+        let input = b"0 \xff\xfe a\n\xff 1 b\n2\xff c\n3 \xc3\xa4 d \xc3\n";
+        let (output, result) = process_with_policy(input, ErrorPolicy::Abort);
+        assert_eq!(
+            output,
+            [
+                &b"0 \xff\xfe a\n"[..],
+                b"\xff 1 b\n",
+                b"2\xff c\n",
+                b"3 \xc3\xa4 d \xc3\n"
+            ]
+        );
+        assert!(result.is_ok());
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn process_line_terminators() {
+        let input = b"0 a\r\n1 b\nc\r\n\r\n\n2 d";
+        let (output, result) = process_with_policy(input, ErrorPolicy::Abort);
+        assert_eq!(
+            output,
+            [&b"0 a\r\n"[..], b"1 b\n", b"c\r\n", b"\r\n", b"\n", b"1 d"]
+        );
+        assert!(result.is_ok());
+        let (output, _) = process_with_policy(b"0 a\n1\r\n", ErrorPolicy::Abort);
+        assert_eq!(output, [&b"0 a\n"[..], b"1\r\n"]);
+    }
 
-    fn check_process_text(
-        informat: Option<InputFormat>,
-        outformat: OutputFormat,
-        input: &str,
-        expected_output: Vec<(&str, &str)>,
-    ) {
-        let cursor = io::Cursor::new(input);
-        let mut expected_iter = expected_output.iter();
-        process_text(informat, outformat, cursor, |time, text| {
-            assert_eq!(
-                &(time, text),
-                expected_iter.next().expect("produced too many lines")
-            )
-        });
+    #[test]
+    fn test_error_policy_from_str() {
+        assert_eq!("skip".parse(), Ok(ErrorPolicy::Skip));
+        assert_eq!("pass".parse(), Ok(ErrorPolicy::Pass));
+        assert_eq!("abort".parse(), Ok(ErrorPolicy::Abort));
+        assert!("ignore".parse::<ErrorPolicy>().is_err());
     }
 
     #[test]
@@ -225,11 +1641,14 @@ mod tests {
             OutputFormat::Iso8601 {
                 prec: Precision(0),
                 time_only: false,
+                zone: Zone::Utc,
+                offset: false,
+                comma: false,
             },
             "000.0 a line\n60.66 another line\n",
             vec![
-                ("1970-01-01T00:00:00", " a line"),
-                ("1970-01-01T00:01:00", " another line"),
+                ("", "1970-01-01T00:00:00", " a line"),
+                ("", "1970-01-01T00:01:00", " another line"),
             ],
         );
     }
@@ -241,12 +1660,15 @@ mod tests {
             OutputFormat::Iso8601 {
                 prec: Precision(0),
                 time_only: false,
+                zone: Zone::Utc,
+                offset: false,
+                comma: false,
             },
             "000.0 a line\nanother line\n\n",
             vec![
-                ("1970-01-01T00:00:00", " a line"),
-                ("", "another line"),
-                ("", ""),
+                ("", "1970-01-01T00:00:00", " a line"),
+                ("", "", "another line"),
+                ("", "", ""),
             ],
         );
     }
@@ -257,7 +1679,11 @@ mod tests {
             Some(InputFormat::Unix),
             OutputFormat::Unix(Unit::Seconds, Precision(0)),
             "10.0 first\n11.0 second\n13.0 third\n",
-            vec![("10", " first"), ("11", " second"), ("13", " third")],
+            vec![
+                ("", "10", " first"),
+                ("", "11", " second"),
+                ("", "13", " third"),
+            ],
         );
     }
 
@@ -268,12 +1694,15 @@ mod tests {
             OutputFormat::Iso8601 {
                 prec: Precision(0),
                 time_only: false,
+                zone: Zone::Utc,
+                offset: false,
+                comma: false,
             },
             "10.0 first\n11.0 second\n13.0 third\n",
             vec![
-                ("1970-01-01T00:00:10", " first"),
-                ("1970-01-01T00:00:11", " second"),
-                ("1970-01-01T00:00:13", " third"),
+                ("", "1970-01-01T00:00:10", " first"),
+                ("", "1970-01-01T00:00:11", " second"),
+                ("", "1970-01-01T00:00:13", " third"),
             ],
         );
     }
@@ -282,20 +1711,51 @@ mod tests {
     fn process_delta() {
         check_process_text(
             Some(InputFormat::Unix),
-            OutputFormat::Delta(Unit::Seconds, Precision(0)),
+            OutputFormat::Delta {
+                unit: Unit::Seconds,
+                prec: Precision(0),
+                mark: false,
+            },
             "10.0 first\n11.0 second\n13.0 third\n",
-            vec![("0", " first"), ("1", " second"), ("2", " third")],
+            vec![
+                ("", "0", " first"),
+                ("", "1", " second"),
+                ("", "2", " third"),
+            ],
         );
         check_process_text(
             Some(InputFormat::Unix),
-            OutputFormat::Delta(Unit::Seconds, Precision(0)),
+            OutputFormat::Delta {
+                unit: Unit::Seconds,
+                prec: Precision(0),
+                mark: false,
+            },
             "invalid line\n10.0 first\n11.0 second\ninvalid line\n13.0 third\n",
             vec![
-                ("", "invalid line"),
-                ("0", " first"),
-                ("1", " second"),
-                ("", "invalid line"),
-                ("2", " third"),
+                ("", "", "invalid line"),
+                ("", "0", " first"),
+                ("", "1", " second"),
+                ("", "", "invalid line"),
+                ("", "2", " third"),
+            ],
+        );
+    }
+
+    #[test]
+    fn process_delta_out_of_order() {
+        check_process_text(
+            Some(InputFormat::Unix),
+            OutputFormat::Delta {
+                unit: Unit::Milliseconds,
+                prec: Precision(0),
+                mark: true,
+            },
+            "10.0 first\n11.5 second\n10.25 third\n12.0 fourth\n",
+            vec![
+                ("", "0", " first"),
+                ("", "1500", " second"),
+                ("", "!-1250", " third"),
+                ("", "1750", " fourth"),
             ],
         );
     }
@@ -306,18 +1766,262 @@ mod tests {
             Some(InputFormat::Unix),
             OutputFormat::Elapsed(Unit::Seconds, Precision(0)),
             "10.0 first\n11.0 second\n13.0 third\n",
-            vec![("0", " first"), ("1", " second"), ("3", " third")],
+            vec![
+                ("", "0", " first"),
+                ("", "1", " second"),
+                ("", "3", " third"),
+            ],
         );
         check_process_text(
             Some(InputFormat::Unix),
             OutputFormat::Elapsed(Unit::Seconds, Precision(0)),
             "invalid line\n10.0 first\n11.0 second\ninvalid line\n13.0 third\n",
             vec![
-                ("", "invalid line"),
-                ("0", " first"),
-                ("1", " second"),
-                ("", "invalid line"),
-                ("3", " third"),
+                ("", "", "invalid line"),
+                ("", "0", " first"),
+                ("", "1", " second"),
+                ("", "", "invalid line"),
+                ("", "3", " third"),
+            ],
+        );
+    }
+
+    #[test]
+    fn process_elapsed_zero() {
+        let elapsed = Output::new(
+            OutputFormat::Elapsed(Unit::Seconds, Precision(0)),
+            ErrorPolicy::Abort,
+        );
+        assert_eq!(
+            process_continuous(
+                &["10 a\n12 b\n", "15 c\n"],
+                Output {
+                    first_time: Some(parse_time_arg("1970-01-01T00:00:12").unwrap()),
+                    ..elapsed.clone()
+                }
+            ),
+            ["-2 a\n", "0 b\n", "3 c\n"]
+        );
+        let zero_line = Output {
+            zero_line: Some(Regex::new("Starting").unwrap()),
+            ..elapsed
+        };
+        assert_eq!(
+            process_continuous(&["x\n10 a\n12 Starting\n", "15 c\n"], zero_line.clone()),
+            ["x\n", "-2 a\n", "0 Starting\n", "3 c\n"]
+        );
+        // Zero point is the next timestamp after an untimed matching line.
+        assert_eq!(
+            process_continuous(&["10 a\nStarting\n  more\n13 b\n"], zero_line.clone()),
+            ["-3 a\n", "Starting\n", "  more\n", "0 b\n"]
+        );
+        // Without a match, elapsed time is measured from the first timestamp.
+        assert_eq!(
+            process_continuous(&["10 a\n12 b\n"], zero_line),
+            ["0 a\n", "2 b\n"]
+        );
+    }
+
+    #[test]
+    fn process_time_window() {
+        let time = |s| Some(Bound::Time(parse_time_arg(s).unwrap()));
+        let output = Output::new(
+            OutputFormat::Unix(Unit::Seconds, Precision(0)),
+            ErrorPolicy::Abort,
+        );
+        let input = "header\n10 a\n  more\n12 b\n  more\n14 c\n16 d\n  more\n";
+        assert_eq!(
+            process_continuous(
+                &[input],
+                Output {
+                    since: time("1970-01-01T00:00:11"),
+                    until: time("1970-01-01T00:00:14"),
+                    ..output.clone()
+                }
+            ),
+            ["12 b\n", "  more\n", "14 c\n"]
+        );
+        // Lines before the first timestamp go with the first line that has one.
+        assert_eq!(
+            process_continuous(
+                &[input],
+                Output {
+                    until: time("1970-01-01T00:00:10"),
+                    ..output.clone()
+                }
+            ),
+            ["header\n", "10 a\n", "  more\n"]
+        );
+        assert_eq!(
+            process_continuous(
+                &["10 a\n", "13 b\n", "16 c\n  more\n"],
+                Output {
+                    since: Some(Bound::FromEnd(Duration::seconds(-3))),
+                    ..output.clone()
+                }
+            ),
+            ["13 b\n", "16 c\n", "  more\n"]
+        );
+        // Resolved from the last timestamp read ahead, the bound holds nothing back.
+        let mut resolved = Output {
+            since: Some(Bound::FromEnd(Duration::seconds(-3))),
+            until: Some(Bound::FromEnd(Duration::seconds(-3))),
+            ..output.clone()
+        };
+        resolved.resolve_bounds(parse_time_arg("1970-01-01T00:00:16").ok());
+        assert_eq!(
+            (resolved.since, resolved.until),
+            (time("1970-01-01T00:00:13"), time("1970-01-01T00:00:13"))
+        );
+        assert_eq!(
+            process_continuous(&["10 a\n", "13 b\n", "16 c\n  more\n"], resolved),
+            ["13 b\n"]
+        );
+        resolved = Output {
+            since: Some(Bound::FromEnd(Duration::seconds(-3))),
+            ..output.clone()
+        };
+        resolved.resolve_bounds(None);
+        assert_eq!(resolved.since, None);
+        assert_eq!(
+            process_continuous(
+                &["x\ny\n"],
+                Output {
+                    since: time("1970-01-01T00:00:11"),
+                    ..output
+                }
+            ),
+            ["x\n", "y\n"]
+        );
+    }
+
+    #[test]
+    fn test_parse_bound() {
+        let opts = ParseOptions::default();
+        assert_eq!(
+            Bound::parse("-10m", None, &opts),
+            Some(Bound::FromEnd(Duration::minutes(-10)))
+        );
+        assert_eq!(
+            Bound::parse("2022-03-20T15:32:01+02:00", None, &opts),
+            Some(Bound::Time(parse_time_arg("2022-03-20T13:32:01").unwrap()))
+        );
+        assert_eq!(
+            Bound::parse("-1.5", Some(&InputFormat::Unix), &opts),
+            Some(Bound::Time(
+                parse_time_arg("1969-12-31T23:59:58.5").unwrap()
+            ))
+        );
+        assert_eq!(Bound::parse("10m", None, &opts), None);
+        assert_eq!(Bound::parse("yesterday", None, &opts), None);
+    }
+
+    #[test]
+    fn process_elapsed_across_dst() {
+        // Clocks were turned from 04:00 to 03:00 on 2022-10-30 in Helsinki.
+        check_process_text_with(
+            Some(InputFormat::Iso8601),
+            &ParseOptions {
+                zone: Zone::Named(chrono_tz::Europe::Helsinki),
+                dst: DstPolicy::Earliest,
+                ..Default::default()
+            },
+            OutputFormat::Elapsed(Unit::Seconds, Precision(0)),
+            "2022-10-30T02:30:00 first\n2022-10-30T03:30:00 second\n2022-10-30T04:30:00 third\n",
+            vec![
+                ("", "0", " first"),
+                ("", "3600", " second"),
+                ("", "10800", " third"),
+            ],
+        );
+    }
+
+    #[test]
+    fn process_brackets() {
+        check_process_text(
+            None,
+            OutputFormat::Delta {
+                unit: Unit::Milliseconds,
+                prec: Precision(0),
+                mark: false,
+            },
+            "[    0.000000] first\n[    0.250000] second\n",
+            vec![("[    ", "0", "] first"), ("[    ", "250", "] second")],
+        );
+    }
+
+    #[test]
+    fn process_located() {
+        check_process_text_with(
+            None,
+            &ParseOptions {
+                locator: Locator::Regex(Regex::new(r"^\[\S+\] (.*?) -").unwrap()),
+                ..Default::default()
+            },
+            OutputFormat::Unix(Unit::Seconds, Precision(0)),
+            "[a] 1970-01-01 00:00:10 - first\n[b] - second\n[c] 1970-01-01T00:00:13 - third\n",
+            vec![
+                ("[a] ", "10", " - first"),
+                ("", "", "[b] - second"),
+                ("[c] ", "13", " - third"),
+            ],
+        );
+    }
+
+    #[test]
+    fn process_clf() {
+        check_process_text_with(
+            Some(InputFormat::Clf),
+            &ParseOptions {
+                locator: InputFormat::Clf.default_locator(),
+                ..Default::default()
+            },
+            OutputFormat::Elapsed(Unit::Seconds, Precision(0)),
+            "h - - [20/Mar/2022:15:32:01 +0000] \"GET / HTTP/1.1\" 200\n\
+             h - - [20/Mar/2022:17:32:11 +0200] \"GET /a HTTP/1.1\" 404\n",
+            vec![
+                ("h - - [", "0", "] \"GET / HTTP/1.1\" 200"),
+                ("h - - [", "10", "] \"GET /a HTTP/1.1\" 404"),
+            ],
+        );
+    }
+
+    #[test]
+    fn auto_detect_clf() {
+        check_process_text(
+            None,
+            OutputFormat::Unix(Unit::Seconds, Precision(0)),
+            "127.0.0.1 - - [20/Mar/2022:15:32:01 +0000] \"GET / HTTP/1.1\" 200 512\n",
+            vec![(
+                "127.0.0.1 - - [",
+                "1647790321",
+                "] \"GET / HTTP/1.1\" 200 512",
+            )],
+        );
+    }
+
+    #[test]
+    fn process_syslog_year_rollover() {
+        check_process_text_with(
+            Some(InputFormat::Syslog),
+            &ParseOptions {
+                year: 2021,
+                latest: None,
+                ..Default::default()
+            },
+            OutputFormat::Iso8601 {
+                prec: Precision(0),
+                time_only: false,
+                zone: Zone::Utc,
+                offset: false,
+                comma: false,
+            },
+            "Dec 31 23:59:59 first\nDec 31 23:59:58 second\nJan  1 00:00:01 third\nMar  1 00:00:00 fourth\n",
+            vec![
+                ("", "2021-12-31T23:59:59", " first"),
+                ("", "2021-12-31T23:59:58", " second"),
+                ("", "2022-01-01T00:00:01", " third"),
+                ("", "2022-03-01T00:00:00", " fourth"),
             ],
         );
     }
@@ -329,11 +2033,14 @@ mod tests {
             OutputFormat::Iso8601 {
                 prec: Precision(0),
                 time_only: false,
+                zone: Zone::Utc,
+                offset: false,
+                comma: false,
             },
             "000.0 a line\n60.66 another line\n",
             vec![
-                ("1970-01-01T00:00:00", " a line"),
-                ("1970-01-01T00:01:00", " another line"),
+                ("", "1970-01-01T00:00:00", " a line"),
+                ("", "1970-01-01T00:01:00", " another line"),
             ],
         );
     }
@@ -345,53 +2052,368 @@ mod tests {
             OutputFormat::Iso8601 {
                 prec: Precision(0),
                 time_only: false,
+                zone: Zone::Utc,
+                offset: false,
+                comma: false,
             },
             "notime\nstillno\n000.0 a line\n60.66 another line\n",
             vec![
-                ("", "notime"),
-                ("", "stillno"),
-                ("1970-01-01T00:00:00", " a line"),
-                ("1970-01-01T00:01:00", " another line"),
+                ("", "", "notime"),
+                ("", "", "stillno"),
+                ("", "1970-01-01T00:00:00", " a line"),
+                ("", "1970-01-01T00:01:00", " another line"),
             ],
         );
     }
 
+    #[test]
+    fn process_shift() {
+        check_process_text_with_state(
+            InputState {
+                shift: Duration::milliseconds(-1500),
+                ..InputState::new(Some(InputFormat::Unix), ParseOptions::default())
+            },
+            OutputFormat::Unix(Unit::Seconds, Precision(1)),
+            "10 a\nb\n12.5 c\n",
+            vec![("", "8.5", " a"), ("", "", "b"), ("", "11.0", " c")],
+        );
+    }
+
+    #[test]
+    fn process_drift() {
+        // 100 ppm fast clock, 2 s late at the first line.
+        check_process_text_with_state(
+            InputState {
+                drift: Some(100.0),
+                shift: Duration::seconds(2),
+                ..InputState::new(Some(InputFormat::Unix), ParseOptions::default())
+            },
+            OutputFormat::Unix(Unit::Seconds, Precision(3)),
+            "1000 a\n11000 b\n",
+            vec![("", "1002.000", " a"), ("", "11001.000", " b")],
+        );
+    }
+
+    #[test]
+    fn process_shift_overflow() {
+        let mut state = InputState {
+            shift: Duration::days(1_000_000_000),
+            ..InputState::new(Some(InputFormat::Unix), ParseOptions::default())
+        };
+        let mut output = vec![];
+        let result = process_text(
+            &mut state,
+            &mut Output::new(
+                OutputFormat::Unix(Unit::Seconds, Precision(0)),
+                ErrorPolicy::Pass,
+            ),
+            0,
+            &b"1000 a\nb\n"[..],
+            |_, prefix, time, text| {
+                output.push([prefix.as_bytes(), time.as_bytes(), text].concat());
+                Ok(())
+            },
+        );
+        assert!(result.is_ok());
+        assert_eq!(output, vec![b"1000 a\n".to_vec(), b"b\n".to_vec()]);
+
+        let result = process_text(
+            &mut state,
+            &mut Output::new(
+                OutputFormat::Unix(Unit::Seconds, Precision(0)),
+                ErrorPolicy::Abort,
+            ),
+            0,
+            &b"1000 a\n"[..],
+            |_, _, _, _| Ok(()),
+        );
+        match result {
+            Err((0, ProcessError::Line(1, msg))) => assert_eq!(msg, "Corrected time out of range"),
+            r => panic!("unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_drift_arg() {
+        let args =
+            Args::try_parse_from(["khronos", "--drift", "-12.5", "--drift=a=b.log=50"]).unwrap();
+        assert_eq!(
+            args.drift,
+            vec![(None, -12.5), (Some("a=b.log".to_string()), 50.0)]
+        );
+        assert!(Args::try_parse_from(["khronos", "--drift", "NaN"]).is_err());
+        assert!(Args::try_parse_from(["khronos", "--drift", "inf"]).is_err());
+        assert!(Args::try_parse_from(["khronos", "--drift", "-1000000"]).is_err());
+        assert!(Args::try_parse_from(["khronos", "--drift", "x"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_anchor() {
+        let opts = ParseOptions::default();
+        let (file, a) = parse_anchor("1970-01-01T00:00:00=1970-01-01T00:00:01", &opts).unwrap();
+        assert_eq!(file, None);
+        let (_, b) = parse_anchor("1970-01-01T00:01:40+00:00=1970-01-01T00:01:40", &opts).unwrap();
+        assert_eq!(DriftCorrection::from_anchors(a, b).unwrap().scale, 0.99);
+        assert!(parse_anchor("1970-01-01T00:00:00", &opts).is_err());
+        let (file, c) =
+            parse_anchor("a.log=1970-01-01T00:00:00=1970-01-01T00:00:01", &opts).unwrap();
+        assert_eq!((file.as_deref(), c), (Some("a.log"), a));
+        assert!(parse_anchor("a.log=1970-01-01T00:00:00", &opts).is_err());
+
+        // Both times are in --intz, like the timestamps of the input.
+        let helsinki = ParseOptions {
+            zone: Zone::Named(chrono_tz::Europe::Helsinki),
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_anchor("2022-03-20T12:00:00=2022-03-20T12:00:01", &helsinki),
+            parse_anchor("2022-03-20T10:00:00=2022-03-20T10:00:01", &opts)
+        );
+    }
+
+    #[test]
+    fn test_shift_arg() {
+        let args =
+            Args::try_parse_from(["khronos", "--shift", "-1h2m", "--shift", "-=2s"]).unwrap();
+        assert_eq!(
+            args.shift,
+            vec![
+                (None, -Duration::minutes(62)),
+                (Some("-".to_string()), Duration::seconds(2))
+            ]
+        );
+        assert!(Args::try_parse_from(["khronos", "--shift", "1x"]).is_err());
+        assert!(Args::try_parse_from(["khronos", "--shift", "a.log=1x"]).is_err());
+    }
+
+    #[test]
+    fn test_for_input() {
+        let values = [
+            (Some("a.log".to_string()), 1),
+            (None, 2),
+            (Some("a.log".to_string()), 3),
+            (None, 4),
+        ];
+        assert_eq!(for_input(&values, "a.log"), Some(3));
+        assert_eq!(for_input(&values, "b.log"), Some(4));
+        assert_eq!(for_input(&values[..1], "b.log"), None);
+        assert_eq!(given_for(&values, Some("b.log")), None);
+        assert_eq!(given_for(&values, None), Some(4));
+    }
+
     #[test]
     fn verify_app() {
-        use clap::CommandFactory;
         Args::command().debug_assert();
     }
 
+    #[test]
+    fn test_parse_input_format() {
+        let opts = ParseOptions::default();
+        assert_eq!(parse_input_format("unix", &opts), Ok(InputFormat::Unix));
+        assert_eq!(parse_input_format("unixms", &opts), Ok(InputFormat::UnixMs));
+        assert_eq!(parse_input_format("unixus", &opts), Ok(InputFormat::UnixUs));
+        assert_eq!(parse_input_format("unixns", &opts), Ok(InputFormat::UnixNs));
+        assert_eq!(parse_input_format("iso", &opts), Ok(InputFormat::Iso8601));
+        assert_eq!(parse_input_format("clf", &opts), Ok(InputFormat::Clf));
+        assert_eq!(
+            parse_input_format("custom:%d/%m/%Y-%H:%M:%S", &opts),
+            Ok(InputFormat::Custom("%d/%m/%Y-%H:%M:%S".to_string()))
+        );
+        assert_eq!(
+            parse_input_format("since:2022-03-20T00:00:00.5", &opts),
+            Ok(InputFormat::Epoc(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2022, 3, 20).unwrap(),
+                NaiveTime::from_hms_milli_opt(0, 0, 0, 500).unwrap()
+            )))
+        );
+        assert!(parse_input_format("custom:", &opts).is_err());
+        assert!(parse_input_format("custom:%Y-%m-%d %Q", &opts).is_err());
+        assert!(parse_input_format("custom:%H:%M:%S", &opts).is_err());
+        assert!(parse_input_format("custom:literal", &opts).is_err());
+        assert!(parse_input_format("custom:%Y-%m-%d %H:%M %z", &opts).is_ok());
+        assert_eq!(
+            parse_input_format("since:2022-03-20T02:00:00+02:00", &opts),
+            Ok(InputFormat::Epoc(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2022, 3, 20).unwrap(),
+                NaiveTime::from_hms_opt(0, 0, 0).unwrap()
+            )))
+        );
+        let helsinki = ParseOptions {
+            zone: Zone::Named(chrono_tz::Europe::Helsinki),
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_input_format("since:2022-03-20T02:00:00", &helsinki),
+            parse_input_format("since:2022-03-20T00:00:00", &opts)
+        );
+        assert_eq!(
+            parse_input_format("since:2022-03-20T02:00:00+02:00", &helsinki),
+            parse_input_format("since:2022-03-20T00:00:00", &opts)
+        );
+        assert!(parse_input_format("since:2022-03-20", &opts).is_err());
+        assert_eq!(
+            parse_input_format("dmesg:2022-03-20 10:15:02", &opts),
+            Ok(InputFormat::Epoc(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2022, 3, 20).unwrap(),
+                NaiveTime::from_hms_opt(10, 15, 2).unwrap()
+            )))
+        );
+        assert_eq!(
+            parse_input_format("dmesg:1.5@2022-03-20T10:15:02", &opts),
+            Ok(InputFormat::Epoc(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2022, 3, 20).unwrap(),
+                NaiveTime::from_hms_milli_opt(10, 15, 0, 500).unwrap()
+            )))
+        );
+        assert!(parse_input_format("dmesg:x@2022-03-20T10:15:02", &opts).is_err());
+        assert!(parse_input_format("dmesg:1.5@foo", &opts).is_err());
+        assert!(parse_input_format("foo", &opts).is_err());
+    }
+
+    #[test]
+    fn process_since() {
+        check_process_text(
+            Some(InputFormat::Epoc(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2022, 3, 20).unwrap(),
+                NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+            ))),
+            OutputFormat::Iso8601 {
+                prec: Precision(3),
+                time_only: false,
+                zone: Zone::Utc,
+                offset: false,
+                comma: false,
+            },
+            "1.5 first\n3600 second\n",
+            vec![
+                ("", "2022-03-20T00:00:01.500", " first"),
+                ("", "2022-03-20T01:00:00.000", " second"),
+            ],
+        );
+    }
+
     #[test]
     fn test_parse_output_format_iso8601() {
         assert_eq!(
             parse_output_format("iso"),
             Ok(OutputFormat::Iso8601 {
                 prec: Precision(0),
-                time_only: false
+                time_only: false,
+                zone: Zone::Utc,
+                offset: false,
+                comma: false,
             })
         );
         assert_eq!(
             parse_output_format("iso,.1"),
             Ok(OutputFormat::Iso8601 {
                 prec: Precision(1),
-                time_only: false
+                time_only: false,
+                zone: Zone::Utc,
+                offset: false,
+                comma: false,
             })
         );
         assert_eq!(
             parse_output_format("iso,.3"),
             Ok(OutputFormat::Iso8601 {
                 prec: Precision(3),
-                time_only: false
+                time_only: false,
+                zone: Zone::Utc,
+                offset: false,
+                comma: false,
             })
         );
         assert_eq!(
             parse_output_format("iso,.3,nodate"),
             Ok(OutputFormat::Iso8601 {
                 prec: Precision(3),
-                time_only: true
+                time_only: true,
+                zone: Zone::Utc,
+                offset: false,
+                comma: false,
+            })
+        );
+        assert_eq!(
+            parse_output_format("iso,comma,.3"),
+            Ok(OutputFormat::Iso8601 {
+                prec: Precision(3),
+                time_only: false,
+                zone: Zone::Utc,
+                offset: false,
+                comma: true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_output_format_iso8601_zone() {
+        assert_eq!(
+            parse_output_format("iso,local"),
+            Ok(OutputFormat::Iso8601 {
+                prec: Precision(0),
+                time_only: false,
+                zone: Zone::Local,
+                offset: false,
+                comma: false,
+            })
+        );
+        assert_eq!(
+            parse_output_format("iso,tz=Europe/Helsinki,offset,.3"),
+            Ok(OutputFormat::Iso8601 {
+                prec: Precision(3),
+                time_only: false,
+                zone: Zone::Named(chrono_tz::Europe::Helsinki),
+                offset: true,
+                comma: false,
+            })
+        );
+        assert_eq!(
+            parse_output_format("iso,utc,offset"),
+            Ok(OutputFormat::Iso8601 {
+                prec: Precision(0),
+                time_only: false,
+                zone: Zone::Utc,
+                offset: true,
+                comma: false,
+            })
+        );
+        assert!(parse_output_format("iso,tz=Mars/Olympus").is_err());
+    }
+
+    #[test]
+    fn test_parse_output_format_rfc5424() {
+        assert_eq!(
+            parse_output_format("rfc5424"),
+            Ok(OutputFormat::Rfc5424 { zone: Zone::Utc })
+        );
+        assert_eq!(
+            parse_output_format("rfc5424,local"),
+            Ok(OutputFormat::Rfc5424 { zone: Zone::Local })
+        );
+        assert!(parse_output_format("rfc5424,.3").is_err());
+    }
+
+    #[test]
+    fn test_parse_output_format_custom() {
+        assert_eq!(
+            parse_output_format("fmt:%b %d %H:%M:%S"),
+            Ok(OutputFormat::Custom {
+                fmt: "%b %d %H:%M:%S".to_string(),
+                zone: Zone::Utc
+            })
+        );
+        assert_eq!(
+            parse_output_format("fmt,tz=Europe/Helsinki:%Y,%m,%d"),
+            Ok(OutputFormat::Custom {
+                fmt: "%Y,%m,%d".to_string(),
+                zone: Zone::Named(chrono_tz::Europe::Helsinki)
             })
         );
+        assert!(parse_output_format("fmt:").is_err());
+        assert!(parse_output_format("fmt:%Q").is_err());
+        assert!(parse_output_format("fmt,.3:%H").is_err());
+        assert!(parse_output_format("fmt,local").is_err());
     }
 
     #[test]
@@ -434,11 +2456,27 @@ mod tests {
     fn test_parse_output_format_delta() {
         assert_eq!(
             parse_output_format("delta,ms"),
-            Ok(OutputFormat::Delta(Unit::Milliseconds, Precision(0)))
+            Ok(OutputFormat::Delta {
+                unit: Unit::Milliseconds,
+                prec: Precision(0),
+                mark: false,
+            })
         );
         assert_eq!(
             parse_output_format("delta,.9"),
-            Ok(OutputFormat::Delta(Unit::Seconds, Precision(9)))
+            Ok(OutputFormat::Delta {
+                unit: Unit::Seconds,
+                prec: Precision(9),
+                mark: false,
+            })
+        );
+        assert_eq!(
+            parse_output_format("delta,mark,ms"),
+            Ok(OutputFormat::Delta {
+                unit: Unit::Milliseconds,
+                prec: Precision(0),
+                mark: true,
+            })
         );
     }
 
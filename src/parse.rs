@@ -1,4 +1,7 @@
-use chrono::{Duration, NaiveDateTime};
+use crate::{DstPolicy, Locator, Zone};
+use chrono::format::{self, Parsed, StrftimeItems};
+use chrono::{DateTime, Duration, NaiveDateTime};
+use std::borrow::Cow;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum InputFormat {
@@ -6,15 +9,102 @@ pub enum InputFormat {
     Unix,
     /// Milliseconds since midnight 1970-01-01
     UnixMs,
-    /// E.g. "%Y-%m-%d %H:%M". Date, hour and minute fields are mandatory.
+    /// Microseconds since midnight 1970-01-01
+    UnixUs,
+    /// Nanoseconds since midnight 1970-01-01
+    UnixNs,
+    /// Seconds since given UTC time, e.g. boot time of the machine
     Epoc(NaiveDateTime),
+    /// E.g. "2022-03-20T15:32:01.462". May end with an UTC offset ("Z", "+02:00" or "+0200"), in
+    /// which case the timestamp is converted to UTC.
     Iso8601,
+    /// BSD syslog (RFC 3164), e.g. "Mar 20 15:32:01". The year is taken from options.
+    Syslog,
+    /// RFC 5424 syslog timestamp, i.e. RFC 3339, e.g. "2022-03-20T15:32:01.462Z". The UTC offset is
+    /// mandatory.
+    Rfc5424,
+    /// Apache/nginx Common Log Format, e.g. "20/Mar/2022:15:32:01 +0000". In the log the timestamp
+    /// is enclosed in brackets in the fourth field.
+    Clf,
+    /// E.g. "%Y-%m-%d %H:%M". Date, hour and minute fields are mandatory.
     Custom(String),
 }
 
+/// Settings affecting how parsed timestamps are interpreted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Time zone of timestamps that have no UTC offset.
+    pub zone: Zone,
+    /// How to resolve local times made ambiguous or nonexistent by DST transitions.
+    pub dst: DstPolicy,
+    /// Where to look for the timestamp in a line.
+    pub locator: Locator,
+    /// Year of timestamps that don't include one, e.g. BSD syslog. It is not advanced when
+    /// parsing, so callers parsing consecutive lines must do that when the timestamps wrap around
+    /// at new year.
+    pub year: i32,
+    /// Timestamps without a year are moved to the previous year if they would otherwise be more
+    /// than a day after this time, e.g. the modification time of the log file.
+    pub latest: Option<NaiveDateTime>,
+}
+
+/// UTC timestamps in the beginning of the line. Timestamps without a year are in 1970; the
+/// defaults don't depend on the current time.
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            zone: Zone::Utc,
+            dst: DstPolicy::Earliest,
+            locator: Locator::Start,
+            year: 1970,
+            latest: None,
+        }
+    }
+}
+
+impl InputFormat {
+    /// Returns where timestamps of this format are looked for unless told otherwise.
+    pub fn default_locator(&self) -> Locator {
+        match self {
+            InputFormat::Clf => Locator::Field {
+                index: 4,
+                delimiter: None,
+            },
+            _ => Locator::Start,
+        }
+    }
+
+    /// Whether timestamps of this format may follow a syslog header ("<PRI>" optionally followed
+    /// by "VERSION "), i.e. the format is a syslog one or ISO 8601, which RFC 5424 timestamps are
+    /// detected as.
+    pub fn may_follow_syslog_header(&self) -> bool {
+        matches!(
+            self,
+            InputFormat::Syslog | InputFormat::Rfc5424 | InputFormat::Iso8601
+        )
+    }
+}
+
+/// Converts seconds and nanoseconds since the Unix epoch to datetime.
+fn from_timestamp(sec: i64, nsec: u32) -> Option<NaiveDateTime> {
+    DateTime::from_timestamp(sec, nsec).map(|t| t.naive_utc())
+}
+
+/// Converts a number of units since the Unix epoch to datetime. `frac` is the fractional part in
+/// billionths of a unit, and `per_sec` the number of units in a second.
+fn from_units(x: i64, frac: u32, per_sec: i64) -> Option<NaiveDateTime> {
+    let nanos_per_unit = 1_000_000_000 / per_sec;
+    let nsec =
+        x.rem_euclid(per_sec) * nanos_per_unit + frac as i64 * nanos_per_unit / 1_000_000_000;
+    from_timestamp(x.div_euclid(per_sec), nsec as u32)
+}
+
 /// Parses a decimal number into integer and nano parts.
-fn parse_decimal(s: &str) -> Option<(i64, u32)> {
-    Some(match s.find('.') {
+///
+/// The integer part is rounded down and the nano part is always non-negative, e.g. "-1.5" gives
+/// (-2, 500000000).
+pub(crate) fn parse_decimal(s: &str) -> Option<(i64, u32)> {
+    let (int, nanos) = match s.find('.') {
         Some(i) => (s[..i].parse::<i64>().ok()?, {
             // Parse at most 9 digits after the decimal point.
             let f = &s[(i + 1)..(s.len().min(i + 10))];
@@ -22,73 +112,227 @@ fn parse_decimal(s: &str) -> Option<(i64, u32)> {
             n * 10u32.pow(9 - f.len() as u32)
         }),
         None => (s.parse::<i64>().ok()?, 0),
+    };
+    Some(match s.starts_with('-') && nanos > 0 {
+        true => (int.checked_sub(1)?, 1_000_000_000 - nanos),
+        false => (int, nanos),
     })
 }
 
+/// Parses a duration like "+3.25s", "-1h2m" or "500ms". The optional sign applies to the whole
+/// duration. The units are d, h, m, s, ms, us and ns, and each number may have a fraction.
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let (negative, mut rest) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    if rest.is_empty() {
+        return None;
+    }
+    let mut total = Duration::zero();
+    while !rest.is_empty() {
+        let num_len = rest.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+        let unit_len = rest[num_len..]
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len() - num_len);
+        let nanos_per_unit: i64 = match &rest[num_len..num_len + unit_len] {
+            "d" => 86_400_000_000_000,
+            "h" => 3_600_000_000_000,
+            "m" => 60_000_000_000,
+            "s" => 1_000_000_000,
+            "ms" => 1_000_000,
+            "us" => 1_000,
+            "ns" => 1,
+            _ => return None,
+        };
+        let (int, frac) = parse_decimal(&rest[..num_len])?;
+        let frac = (frac as i128 * nanos_per_unit as i128 / 1_000_000_000) as i64;
+        let nanos = int.checked_mul(nanos_per_unit)?.checked_add(frac)?;
+        total = total.checked_add(&Duration::nanoseconds(nanos))?;
+        rest = &rest[num_len + unit_len..];
+    }
+    Some(if negative { -total } else { total })
+}
+
+/// Parses ISO 8601 timestamp with an optional UTC offset. Timestamps without an offset are
+/// assumed to be in the time zone given in options.
+///
+/// Date and time may be separated by a space instead of "T", and fractional seconds by a comma
+/// instead of a dot, e.g. "2022-03-20 15:32:01,462" written by log4j and Python logging.
+fn parse_iso8601(s: &str, opts: &ParseOptions) -> Option<NaiveDateTime> {
+    let s: Cow<str> = match s.contains(',') {
+        true => s.replacen(',', ".", 1).into(),
+        false => s.into(),
+    };
+    // The permissive "%#z" accepts "Z", "±HH:MM" and "±HHMM".
+    for (fmt, fmt_with_offset) in [
+        ("%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f%#z"),
+        ("%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f%#z"),
+    ] {
+        if let Ok(t) = DateTime::parse_from_str(&s, fmt_with_offset) {
+            return Some(t.naive_utc());
+        }
+        if let Ok(t) = NaiveDateTime::parse_from_str(&s, fmt) {
+            return opts.zone.to_utc(t, opts.dst);
+        }
+    }
+    None
+}
+
+/// Parses BSD syslog timestamp, e.g. "Mar 20 15:32:01", using given year.
+fn parse_syslog(s: &str, year: i32) -> Option<NaiveDateTime> {
+    let mut parsed = Parsed::new();
+    format::parse(&mut parsed, s, StrftimeItems::new("%b %e %H:%M:%S%.f")).ok()?;
+    parsed.set_year(year.into()).ok()?;
+    parsed.to_naive_datetime_with_offset(0).ok()
+}
+
+/// Parses Common Log Format timestamp, e.g. "20/Mar/2022:15:32:01 +0000", to UTC.
+fn parse_clf(s: &str) -> Option<NaiveDateTime> {
+    Some(
+        DateTime::parse_from_str(s, "%d/%b/%Y:%H:%M:%S %z")
+            .ok()?
+            .naive_utc(),
+    )
+}
+
 /// Parses string to datetime according to given format.
-pub fn parse_string(s: &str, format: &InputFormat) -> Option<NaiveDateTime> {
+///
+/// The result is in UTC. Timestamps without an UTC offset are converted from the time zone given
+/// in options.
+pub fn parse_string(s: &str, format: &InputFormat, opts: &ParseOptions) -> Option<NaiveDateTime> {
     Some(match format {
         InputFormat::Unix => {
             let (sec, nsec) = parse_decimal(s)?;
-            NaiveDateTime::from_timestamp(sec, nsec)
+            from_timestamp(sec, nsec)?
         }
         InputFormat::UnixMs => {
-            let (msec, psec) = parse_decimal(s)?;
-            NaiveDateTime::from_timestamp(
-                msec / 1000,
-                (msec % 1000) as u32 * 1_000_000 + psec / 1000,
-            )
+            let (msec, frac) = parse_decimal(s)?;
+            from_units(msec, frac, 1_000)?
+        }
+        InputFormat::UnixUs => {
+            let (usec, frac) = parse_decimal(s)?;
+            from_units(usec, frac, 1_000_000)?
+        }
+        InputFormat::UnixNs => {
+            let (nsec, frac) = parse_decimal(s)?;
+            from_units(nsec, frac, 1_000_000_000)?
         }
         InputFormat::Epoc(epoc) => {
             let (sec, nsec) = parse_decimal(s)?;
-            *epoc + Duration::seconds(sec) + Duration::nanoseconds(nsec.into())
+            epoc.checked_add_signed(Duration::try_seconds(sec)?)?
+                .checked_add_signed(Duration::nanoseconds(nsec.into()))?
+        }
+        InputFormat::Iso8601 => parse_iso8601(s, opts)?,
+        InputFormat::Syslog => {
+            let t = opts.zone.to_utc(parse_syslog(s, opts.year)?, opts.dst)?;
+            match opts.latest {
+                Some(latest) if t > latest + Duration::days(1) => opts
+                    .zone
+                    .to_utc(parse_syslog(s, opts.year - 1)?, opts.dst)?,
+                _ => t,
+            }
+        }
+        InputFormat::Rfc5424 => DateTime::parse_from_rfc3339(s).ok()?.naive_utc(),
+        InputFormat::Clf => parse_clf(s)?,
+        InputFormat::Custom(fmt) => {
+            let t = NaiveDateTime::parse_from_str(s, fmt).ok()?;
+            opts.zone.to_utc(t, opts.dst)?
         }
-        InputFormat::Iso8601 => NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f").ok()?,
-        InputFormat::Custom(fmt) => NaiveDateTime::parse_from_str(s, &fmt).ok()?,
     })
 }
 
-/// Parses line to timestamp and remainder.
+/// Parses line to prefix, timestamp and suffix.
 ///
-/// Assumes the timestamp is in the beginning of the line, does not contain whitespace (space or
-/// tab), and is followed by whitespace. This whitespace is included in the remainder.
+/// The timestamp is looked for where the locator in options points to. By default it must be in
+/// the beginning of the line and followed by whitespace (space or tab), which is included in the
+/// suffix. The timestamp may contain whitespace, e.g. "2022-03-20 15:32:01", in which case the
+/// shortest parseable text is used. Brackets around the timestamp, e.g. "[12.345678]", are
+/// included in the prefix and suffix, and so is whitespace inside them.
 ///
-/// If timestamp cannot be parsed, returns None as timestamp and the whole line as the remainder.
-pub fn parse_line<'a>(s: &'a str, format: &InputFormat) -> (Option<NaiveDateTime>, &'a str) {
-    match s.find(&[' ', '\t']) {
-        Some(i) => match parse_string(&s[..i], format) {
-            Some(timestamp) => (Some(timestamp), &s[i..]),
-            None => (None, s),
-        },
-        None => (None, s),
+/// If timestamp cannot be parsed, returns an empty prefix, None as timestamp and the whole line as
+/// the suffix.
+pub fn parse_line<'a>(
+    s: &'a str,
+    format: &InputFormat,
+    opts: &ParseOptions,
+) -> (&'a str, Option<NaiveDateTime>, &'a str) {
+    let syslog_header = format.may_follow_syslog_header();
+    for span in opts.locator.candidates(s, syslog_header) {
+        let ts = s[span.clone()].trim_matches([' ', '\t']);
+        if let Some(timestamp) = parse_string(ts, format, opts) {
+            return (&s[..span.start], Some(timestamp), &s[span.end..]);
+        }
     }
+    ("", None, s)
 }
 
 /// Tries to automatically detect the timestamp format used.
 ///
-/// Assumes the timestamp is in the beginning of the line, does not contain whitespace (space or
-/// tab), and is followed by whitespace.
-pub fn detect_format(s: &str) -> Option<InputFormat> {
-    let ts = &s[..s.find(&[' ', '\t'])?];
+/// The timestamp is looked for where the locator in options points to, like in `parse_line`. With
+/// the default locator, Common Log Format is also looked for where its default locator points to,
+/// which must then be used to parse the lines.
+pub fn detect_format(s: &str, opts: &ParseOptions) -> Option<InputFormat> {
+    let detect = |locator: &Locator| {
+        locator
+            .candidates(s, true)
+            .into_iter()
+            .find_map(|span| detect_timestamp_format(s[span].trim_matches([' ', '\t'])))
+    };
+    detect(&opts.locator).or_else(|| match opts.locator {
+        Locator::Start => {
+            detect(&InputFormat::Clf.default_locator()).filter(|f| *f == InputFormat::Clf)
+        }
+        _ => None,
+    })
+}
+
+/// Parses a timestamp string in the given format, or failing that, in an automatically detected
+/// one.
+pub fn parse_timestamp(
+    s: &str,
+    format: Option<&InputFormat>,
+    opts: &ParseOptions,
+) -> Option<NaiveDateTime> {
+    format
+        .and_then(|format| parse_string(s, format, opts))
+        .or_else(|| parse_string(s, &detect_timestamp_format(s)?, opts))
+}
 
-    if NaiveDateTime::parse_from_str(ts, "%Y-%m-%dT%H:%M:%S%.f").is_ok() {
+/// Detects the format of a timestamp string.
+fn detect_timestamp_format(ts: &str) -> Option<InputFormat> {
+    if parse_iso8601(ts, &ParseOptions::default()).is_some() {
         return Some(InputFormat::Iso8601);
     }
 
-    // 100 billion is 1973-03-03 in if interpreted as milliseconds, 5138-11-16 if interpreted in
-    // seconds. So it's reasonable to assume any bigger timestamps are in milliseconds.
-    match parse_decimal(ts) {
-        Some((x, _)) if x > 100_000_000_000 => return Some(InputFormat::UnixMs),
-        Some(_) => return Some(InputFormat::Unix),
-        None => (),
+    // Use a leap year to accept February 29th.
+    if parse_syslog(ts, 2000).is_some() {
+        return Some(InputFormat::Syslog);
     }
-    None
+
+    if parse_clf(ts).is_some() {
+        return Some(InputFormat::Clf);
+    }
+
+    // 100 billion (12 digits) is 1973-03-03 if interpreted as milliseconds, 5138-11-16 if
+    // interpreted as seconds. So it's reasonable to assume that timestamps of 12 to 14 digits are
+    // in milliseconds, and likewise for each further three digits.
+    let (x, _) = parse_decimal(ts)?;
+    Some(
+        match x.unsigned_abs().checked_ilog10().map_or(1, |n| n + 1) {
+            ..=11 => InputFormat::Unix,
+            12..=14 => InputFormat::UnixMs,
+            15..=17 => InputFormat::UnixUs,
+            _ => InputFormat::UnixNs,
+        },
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use chrono::{NaiveDate, NaiveTime};
+    use regex::Regex;
 
     #[test]
     fn test_decimal_with_nano() {
@@ -101,178 +345,911 @@ mod tests {
         assert_eq!(parse_decimal("foo.123"), None);
     }
 
+    #[test]
+    fn test_negative_decimal() {
+        assert_eq!(parse_decimal("-123"), Some((-123, 0)));
+        assert_eq!(parse_decimal("-1.5"), Some((-2, 500000000)));
+        assert_eq!(parse_decimal("-0.25"), Some((-1, 750000000)));
+        assert_eq!(parse_decimal("-1.000"), Some((-1, 0)));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("3s"), Some(Duration::seconds(3)));
+        assert_eq!(parse_duration("+3.25s"), Some(Duration::milliseconds(3250)));
+        assert_eq!(parse_duration("-1h2m"), Some(-Duration::minutes(62)));
+        assert_eq!(
+            parse_duration("1d1.5h10ms20us30ns"),
+            Some(
+                Duration::days(1)
+                    + Duration::minutes(90)
+                    + Duration::milliseconds(10)
+                    + Duration::microseconds(20)
+                    + Duration::nanoseconds(30)
+            )
+        );
+        assert_eq!(parse_duration("0.5ms"), Some(Duration::microseconds(500)));
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("-"), None);
+        assert_eq!(parse_duration("3"), None);
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("3x"), None);
+        assert_eq!(parse_duration("1h-2m"), None);
+        assert_eq!(parse_duration("1 h"), None);
+        assert_eq!(parse_duration("1000000d"), None);
+    }
+
     #[test]
     fn test_parse_string_unix() {
+        let opts = ParseOptions::default();
         assert_eq!(
-            parse_string("1000", &InputFormat::Unix),
-            Some(NaiveDateTime::from_timestamp(1000, 0))
+            parse_string("1000", &InputFormat::Unix, &opts),
+            from_timestamp(1000, 0)
         );
         assert_eq!(
-            parse_string("1000.000123456", &InputFormat::Unix),
-            Some(NaiveDateTime::from_timestamp(1000, 123456))
+            parse_string("1000.000123456", &InputFormat::Unix, &opts),
+            from_timestamp(1000, 123456)
+        );
+        assert_eq!(parse_string("abc", &InputFormat::Unix, &opts), None);
+        // Before 1970
+        assert_eq!(
+            parse_string("-1.5", &InputFormat::Unix, &opts),
+            from_timestamp(-2, 500_000_000)
+        );
+        assert_eq!(
+            parse_string("-0.5", &InputFormat::Unix, &opts),
+            from_timestamp(-1, 500_000_000)
         );
-        assert_eq!(parse_string("abc", &InputFormat::Unix), None);
     }
 
     #[test]
     fn test_parse_string_unixms() {
+        let opts = ParseOptions::default();
+        assert_eq!(
+            parse_string("1234", &InputFormat::UnixMs, &opts),
+            from_timestamp(1, 234_000_000)
+        );
+        assert_eq!(
+            parse_string("1000.000123456", &InputFormat::UnixMs, &opts),
+            from_timestamp(1, 123)
+        );
+        assert_eq!(parse_string("abc", &InputFormat::UnixMs, &opts), None);
+        // Before 1970
         assert_eq!(
-            parse_string("1234", &InputFormat::UnixMs),
-            Some(NaiveDateTime::from_timestamp(1, 234_000_000))
+            parse_string("-1234", &InputFormat::UnixMs, &opts),
+            from_timestamp(-2, 766_000_000)
         );
         assert_eq!(
-            parse_string("1000.000123456", &InputFormat::UnixMs),
-            Some(NaiveDateTime::from_timestamp(1, 123))
+            parse_string("-1.5", &InputFormat::UnixMs, &opts),
+            from_timestamp(-1, 998_500_000)
+        );
+    }
+
+    #[test]
+    fn test_parse_string_unixus() {
+        let opts = ParseOptions::default();
+        assert_eq!(
+            parse_string("1650400500123456", &InputFormat::UnixUs, &opts),
+            from_timestamp(1650400500, 123_456_000)
+        );
+        assert_eq!(
+            parse_string("1234.5", &InputFormat::UnixUs, &opts),
+            from_timestamp(0, 1_234_500)
+        );
+        assert_eq!(parse_string("abc", &InputFormat::UnixUs, &opts), None);
+    }
+
+    #[test]
+    fn test_parse_string_unixns() {
+        let opts = ParseOptions::default();
+        assert_eq!(
+            parse_string("1650400500123456789", &InputFormat::UnixNs, &opts),
+            from_timestamp(1650400500, 123_456_789)
+        );
+        assert_eq!(
+            parse_string("1234.9", &InputFormat::UnixNs, &opts),
+            from_timestamp(0, 1234)
+        );
+        // Beyond the range of i64
+        assert_eq!(
+            parse_string("10000000000000000000", &InputFormat::UnixNs, &opts),
+            None
         );
-        assert_eq!(parse_string("abc", &InputFormat::UnixMs), None);
     }
 
     #[test]
     fn test_parse_string_epoc() {
+        let opts = ParseOptions::default();
         let epoc = NaiveDateTime::new(
-            NaiveDate::from_ymd(2000, 1, 1),
-            NaiveTime::from_hms(0, 0, 0),
+            NaiveDate::from_ymd_opt(2000, 1, 1).unwrap(),
+            NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
         );
         assert_eq!(
-            parse_string("86460", &InputFormat::Epoc(epoc)),
+            parse_string("86460", &InputFormat::Epoc(epoc), &opts),
             Some(epoc + Duration::days(1) + Duration::minutes(1))
         );
         assert_eq!(
-            parse_string("86460.001", &InputFormat::Epoc(epoc)),
+            parse_string("86460.001", &InputFormat::Epoc(epoc), &opts),
             Some(epoc + Duration::days(1) + Duration::minutes(1) + Duration::milliseconds(1))
         );
-        assert_eq!(parse_string("abc", &InputFormat::Epoc(epoc)), None);
+        assert_eq!(
+            parse_string("-1.25", &InputFormat::Epoc(epoc), &opts),
+            Some(epoc - Duration::milliseconds(1250))
+        );
+        assert_eq!(parse_string("abc", &InputFormat::Epoc(epoc), &opts), None);
+        assert_eq!(
+            parse_string("99999999999999999", &InputFormat::Epoc(epoc), &opts),
+            None
+        );
+        assert_eq!(
+            parse_string("9223372036854775", &InputFormat::Epoc(epoc), &opts),
+            None
+        );
     }
 
     #[test]
     fn test_parse_string_custom() {
+        let opts = ParseOptions::default();
         assert_eq!(
-            parse_string("2001-02-13 12:34", &InputFormat::Custom("%Y-%m-%d %H:%M".to_string())),
+            parse_string(
+                "2001-02-13 12:34",
+                &InputFormat::Custom("%Y-%m-%d %H:%M".to_string()),
+                &opts
+            ),
             Some(NaiveDateTime::new(
-                NaiveDate::from_ymd(2001, 2, 13),
-                NaiveTime::from_hms(12, 34, 0)
+                NaiveDate::from_ymd_opt(2001, 2, 13).unwrap(),
+                NaiveTime::from_hms_opt(12, 34, 0).unwrap()
             ))
         );
         assert_eq!(
             parse_string(
                 "2001-02-13 12:34:56.123456",
-                &InputFormat::Custom("%Y-%m-%d %H:%M:%S%.f".to_string())
+                &InputFormat::Custom("%Y-%m-%d %H:%M:%S%.f".to_string()),
+                &opts
             ),
             Some(NaiveDateTime::new(
-                NaiveDate::from_ymd(2001, 2, 13),
-                NaiveTime::from_hms_micro(12, 34, 56, 123456)
+                NaiveDate::from_ymd_opt(2001, 2, 13).unwrap(),
+                NaiveTime::from_hms_micro_opt(12, 34, 56, 123456).unwrap()
             ))
         );
         assert_eq!(
-            parse_string("2001x02x13 12x34", &InputFormat::Custom("%Y-%m-%d %H:%M".to_string())),
+            parse_string(
+                "2001x02x13 12x34",
+                &InputFormat::Custom("%Y-%m-%d %H:%M".to_string()),
+                &opts
+            ),
             None
         );
         assert_eq!(
-            parse_string("2001x02x13", &InputFormat::Custom("%Y-%m-%d %H:%M".to_string())),
+            parse_string(
+                "2001x02x13",
+                &InputFormat::Custom("%Y-%m-%d %H:%M".to_string()),
+                &opts
+            ),
             None
         );
     }
 
     #[test]
     fn test_parse_string_iso8601() {
+        let opts = ParseOptions::default();
         // With milliseconds
         assert_eq!(
-            parse_string("2001-02-13T12:34:56.123", &InputFormat::Iso8601),
+            parse_string("2001-02-13T12:34:56.123", &InputFormat::Iso8601, &opts),
             Some(NaiveDateTime::new(
-                NaiveDate::from_ymd(2001, 2, 13),
-                NaiveTime::from_hms_milli(12, 34, 56, 123)
+                NaiveDate::from_ymd_opt(2001, 2, 13).unwrap(),
+                NaiveTime::from_hms_milli_opt(12, 34, 56, 123).unwrap()
             ))
         );
         // With nanoseconds
         assert_eq!(
-            parse_string("2001-02-13T12:34:56.123456789", &InputFormat::Iso8601),
+            parse_string(
+                "2001-02-13T12:34:56.123456789",
+                &InputFormat::Iso8601,
+                &opts
+            ),
             Some(NaiveDateTime::new(
-                NaiveDate::from_ymd(2001, 2, 13),
-                NaiveTime::from_hms_nano(12, 34, 56, 123456789)
+                NaiveDate::from_ymd_opt(2001, 2, 13).unwrap(),
+                NaiveTime::from_hms_nano_opt(12, 34, 56, 123456789).unwrap()
             ))
         );
         // No fractional seconds
         assert_eq!(
-            parse_string("2001-02-13T12:34:56", &InputFormat::Iso8601),
+            parse_string("2001-02-13T12:34:56", &InputFormat::Iso8601, &opts),
             Some(NaiveDateTime::new(
-                NaiveDate::from_ymd(2001, 2, 13),
-                NaiveTime::from_hms(12, 34, 56)
+                NaiveDate::from_ymd_opt(2001, 2, 13).unwrap(),
+                NaiveTime::from_hms_opt(12, 34, 56).unwrap()
             ))
         );
+        // UTC designator
+        assert_eq!(
+            parse_string("2001-02-13T12:34:56.123Z", &InputFormat::Iso8601, &opts),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2001, 2, 13).unwrap(),
+                NaiveTime::from_hms_milli_opt(12, 34, 56, 123).unwrap()
+            ))
+        );
+        // Offsets with and without colon are converted to UTC.
+        assert_eq!(
+            parse_string("2001-02-13T12:34:56+02:00", &InputFormat::Iso8601, &opts),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2001, 2, 13).unwrap(),
+                NaiveTime::from_hms_opt(10, 34, 56).unwrap()
+            ))
+        );
+        assert_eq!(
+            parse_string("2001-02-13T12:34:56.5-0530", &InputFormat::Iso8601, &opts),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2001, 2, 13).unwrap(),
+                NaiveTime::from_hms_milli_opt(18, 4, 56, 500).unwrap()
+            ))
+        );
+        assert_eq!(
+            parse_string("2001-02-13T23:34:56-01:00", &InputFormat::Iso8601, &opts),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2001, 2, 14).unwrap(),
+                NaiveTime::from_hms_opt(0, 34, 56).unwrap()
+            ))
+        );
+        // Malformed offsets
+        assert_eq!(
+            parse_string("2001-02-13T12:34:56+2:00", &InputFormat::Iso8601, &opts),
+            None
+        );
+        assert_eq!(
+            parse_string("2001-02-13T12:34:56+02:60", &InputFormat::Iso8601, &opts),
+            None
+        );
         // Space as date-time separator.
         assert_eq!(
-            parse_string("2001-02-13 12:34:56", &InputFormat::Iso8601),
+            parse_string("2001-02-13 12:34:56", &InputFormat::Iso8601, &opts),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2001, 2, 13).unwrap(),
+                NaiveTime::from_hms_opt(12, 34, 56).unwrap()
+            ))
+        );
+        assert_eq!(
+            parse_string("2001-02-13 12:34:56.5+02:00", &InputFormat::Iso8601, &opts),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2001, 2, 13).unwrap(),
+                NaiveTime::from_hms_milli_opt(10, 34, 56, 500).unwrap()
+            ))
+        );
+        // Comma as decimal sign
+        assert_eq!(
+            parse_string("2001-02-13 12:34:56,123", &InputFormat::Iso8601, &opts),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2001, 2, 13).unwrap(),
+                NaiveTime::from_hms_milli_opt(12, 34, 56, 123).unwrap()
+            ))
+        );
+        assert_eq!(
+            parse_string("2001-02-13T12:34:56,5+02:00", &InputFormat::Iso8601, &opts),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2001, 2, 13).unwrap(),
+                NaiveTime::from_hms_milli_opt(10, 34, 56, 500).unwrap()
+            ))
+        );
+        assert_eq!(
+            parse_string("2001-02-13 12:34:56,1,2", &InputFormat::Iso8601, &opts),
+            None
+        );
+        // Date only
+        assert_eq!(
+            parse_string("2001-02-13", &InputFormat::Iso8601, &opts),
             None
         );
     }
 
     #[test]
-    fn test_parse_line() {
-        // Space separator
+    fn test_parse_string_zone() {
+        let opts = ParseOptions {
+            zone: Zone::Named(chrono_tz::Europe::Helsinki),
+            dst: DstPolicy::Reject,
+            ..Default::default()
+        };
+        // Naive timestamps are converted from the input zone.
+        assert_eq!(
+            parse_string("2001-02-13T12:34:56", &InputFormat::Iso8601, &opts),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2001, 2, 13).unwrap(),
+                NaiveTime::from_hms_opt(10, 34, 56).unwrap()
+            ))
+        );
+        assert_eq!(
+            parse_string(
+                "2001-02-13 12:34",
+                &InputFormat::Custom("%Y-%m-%d %H:%M".to_string()),
+                &opts
+            ),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2001, 2, 13).unwrap(),
+                NaiveTime::from_hms_opt(10, 34, 0).unwrap()
+            ))
+        );
+        // Explicit offset overrides the input zone.
+        assert_eq!(
+            parse_string("2001-02-13T12:34:56Z", &InputFormat::Iso8601, &opts),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2001, 2, 13).unwrap(),
+                NaiveTime::from_hms_opt(12, 34, 56).unwrap()
+            ))
+        );
+        // Unix time is not affected.
+        assert_eq!(
+            parse_string("1000", &InputFormat::Unix, &opts),
+            from_timestamp(1000, 0)
+        );
+        // Nonexistent time is rejected.
+        assert_eq!(
+            parse_string("2022-03-27T03:30:00", &InputFormat::Iso8601, &opts),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_string_syslog() {
+        let opts = ParseOptions {
+            year: 2022,
+            latest: None,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_string("Mar 20 15:32:01", &InputFormat::Syslog, &opts),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2022, 3, 20).unwrap(),
+                NaiveTime::from_hms_opt(15, 32, 1).unwrap()
+            ))
+        );
+        // Space padded day
+        assert_eq!(
+            parse_string("Mar  5 15:32:01", &InputFormat::Syslog, &opts),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2022, 3, 5).unwrap(),
+                NaiveTime::from_hms_opt(15, 32, 1).unwrap()
+            ))
+        );
+        // Fractional seconds
+        assert_eq!(
+            parse_string("Mar  5 15:32:01.25", &InputFormat::Syslog, &opts),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2022, 3, 5).unwrap(),
+                NaiveTime::from_hms_milli_opt(15, 32, 1, 250).unwrap()
+            ))
+        );
+        // Not a leap year
+        assert_eq!(
+            parse_string("Feb 29 15:32:01", &InputFormat::Syslog, &opts),
+            None
+        );
+        assert_eq!(
+            parse_string("Foo 20 15:32:01", &InputFormat::Syslog, &opts),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_string_syslog_latest() {
+        let opts = ParseOptions {
+            year: 2022,
+            latest: Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2022, 1, 2).unwrap(),
+                NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+            )),
+            ..Default::default()
+        };
+        // Timestamps after the latest time are moved to the previous year.
+        assert_eq!(
+            parse_string("Dec 31 23:59:59", &InputFormat::Syslog, &opts),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2021, 12, 31).unwrap(),
+                NaiveTime::from_hms_opt(23, 59, 59).unwrap()
+            ))
+        );
+        assert_eq!(
+            parse_string("Jan  1 00:00:00", &InputFormat::Syslog, &opts),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2022, 1, 1).unwrap(),
+                NaiveTime::from_hms_opt(0, 0, 0).unwrap()
+            ))
+        );
+        // Up to a day later is tolerated.
+        assert_eq!(
+            parse_string("Jan  3 11:00:00", &InputFormat::Syslog, &opts),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2022, 1, 3).unwrap(),
+                NaiveTime::from_hms_opt(11, 0, 0).unwrap()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_string_rfc5424() {
+        let opts = ParseOptions::default();
+        assert_eq!(
+            parse_string("2003-10-11T22:14:15.003Z", &InputFormat::Rfc5424, &opts),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2003, 10, 11).unwrap(),
+                NaiveTime::from_hms_milli_opt(22, 14, 15, 3).unwrap()
+            ))
+        );
+        assert_eq!(
+            parse_string(
+                "2003-08-24T05:14:15.000003-07:00",
+                &InputFormat::Rfc5424,
+                &opts
+            ),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2003, 8, 24).unwrap(),
+                NaiveTime::from_hms_micro_opt(12, 14, 15, 3).unwrap()
+            ))
+        );
+        // Offset is mandatory.
+        assert_eq!(
+            parse_string("2003-10-11T22:14:15.003", &InputFormat::Rfc5424, &opts),
+            None
+        );
+        // NILVALUE
+        assert_eq!(parse_string("-", &InputFormat::Rfc5424, &opts), None);
+    }
+
+    #[test]
+    fn test_parse_string_clf() {
+        let opts = ParseOptions::default();
+        assert_eq!(
+            parse_string("20/Mar/2022:15:32:01 +0000", &InputFormat::Clf, &opts),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2022, 3, 20).unwrap(),
+                NaiveTime::from_hms_opt(15, 32, 1).unwrap()
+            ))
+        );
         assert_eq!(
-            parse_line("123.4 Log message", &InputFormat::Unix),
+            parse_string("10/Oct/2000:13:55:36 -0700", &InputFormat::Clf, &opts),
+            Some(NaiveDateTime::new(
+                NaiveDate::from_ymd_opt(2000, 10, 10).unwrap(),
+                NaiveTime::from_hms_opt(20, 55, 36).unwrap()
+            ))
+        );
+        // Offset is mandatory.
+        assert_eq!(
+            parse_string("20/Mar/2022:15:32:01", &InputFormat::Clf, &opts),
+            None
+        );
+        assert_eq!(
+            parse_string("20/Foo/2022:15:32:01 +0000", &InputFormat::Clf, &opts),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_line_clf() {
+        let opts = ParseOptions {
+            locator: InputFormat::Clf.default_locator(),
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_line(
+                r#"127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "GET /a.gif HTTP/1.0" 200 2326"#,
+                &InputFormat::Clf,
+                &opts
+            ),
             (
-                Some(NaiveDateTime::from_timestamp(123, 400_000_000)),
-                " Log message"
+                "127.0.0.1 - frank [",
+                Some(NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2000, 10, 10).unwrap(),
+                    NaiveTime::from_hms_opt(20, 55, 36).unwrap()
+                )),
+                r#"] "GET /a.gif HTTP/1.0" 200 2326"#
             )
         );
-        // Tab separator
         assert_eq!(
-            parse_line("123.4\tLog message", &InputFormat::Unix),
+            detect_format(
+                r#"::1 - - [20/Mar/2022:15:32:01 +0000] "GET / HTTP/1.1" 200 612 "-" "curl/7.81.0""#,
+                &opts
+            ),
+            Some(InputFormat::Clf)
+        );
+    }
+
+    #[test]
+    fn test_parse_line_syslog_header() {
+        let opts = ParseOptions::default();
+        assert_eq!(
+            parse_line(
+                "<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [x] msg",
+                &InputFormat::Rfc5424,
+                &opts
+            ),
             (
-                Some(NaiveDateTime::from_timestamp(123, 400_000_000)),
-                "\tLog message"
+                "<165>1 ",
+                Some(NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2003, 10, 11).unwrap(),
+                    NaiveTime::from_hms_milli_opt(22, 14, 15, 3).unwrap()
+                )),
+                " mymachine.example.com evntslog - ID47 [x] msg"
             )
         );
+        assert_eq!(
+            parse_line(
+                "<165>1 - mymachine.example.com evntslog - ID47 [x] msg",
+                &InputFormat::Rfc5424,
+                &opts
+            ),
+            (
+                "",
+                None,
+                "<165>1 - mymachine.example.com evntslog - ID47 [x] msg"
+            )
+        );
+        let opts = ParseOptions {
+            year: 2022,
+            latest: None,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_line(
+                "<34>Oct 11 22:14:15 mymachine su: msg",
+                &InputFormat::Syslog,
+                &opts
+            ),
+            (
+                "<34>",
+                Some(NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2022, 10, 11).unwrap(),
+                    NaiveTime::from_hms_opt(22, 14, 15).unwrap()
+                )),
+                " mymachine su: msg"
+            )
+        );
+        // Other formats are looked for in the beginning of the line like without a header.
+        assert_eq!(
+            parse_line("<12> 1650400500 x", &InputFormat::Unix, &opts),
+            ("<", from_timestamp(12, 0), "> 1650400500 x")
+        );
+    }
+
+    #[test]
+    fn test_parse_line() {
+        let opts = ParseOptions::default();
+        // Space separator
+        assert_eq!(
+            parse_line("123.4 Log message", &InputFormat::Unix, &opts),
+            ("", from_timestamp(123, 400_000_000), " Log message")
+        );
+        // Tab separator
+        assert_eq!(
+            parse_line("123.4\tLog message", &InputFormat::Unix, &opts),
+            ("", from_timestamp(123, 400_000_000), "\tLog message")
+        );
         // No timestamp, message contains separator.
         assert_eq!(
-            parse_line("Log message", &InputFormat::Unix),
-            (None, "Log message")
+            parse_line("Log message", &InputFormat::Unix, &opts),
+            ("", None, "Log message")
         );
         // No whitespace
         assert_eq!(
-            parse_line("Logmessage", &InputFormat::Unix),
-            (None, "Logmessage")
+            parse_line("Logmessage", &InputFormat::Unix, &opts),
+            ("", None, "Logmessage")
         );
         // Start with space
         assert_eq!(
-            parse_line(" Logmessage", &InputFormat::Unix),
-            (None, " Logmessage")
+            parse_line(" Logmessage", &InputFormat::Unix, &opts),
+            ("", None, " Logmessage")
         );
         // Empty
-        assert_eq!(parse_line("", &InputFormat::Unix), (None, ""));
+        assert_eq!(parse_line("", &InputFormat::Unix, &opts), ("", None, ""));
+    }
+
+    #[test]
+    fn test_parse_line_multiple_words() {
+        let opts = ParseOptions::default();
+        // Space between date and time
+        assert_eq!(
+            parse_line(
+                "2001-02-13 12:34:56.123 Log message",
+                &InputFormat::Iso8601,
+                &opts
+            ),
+            (
+                "",
+                Some(NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2001, 2, 13).unwrap(),
+                    NaiveTime::from_hms_milli_opt(12, 34, 56, 123).unwrap()
+                )),
+                " Log message"
+            )
+        );
+        // Multiple spaces within the timestamp
+        assert_eq!(
+            parse_line(
+                "Sun Mar  6 15:32:01 2022\tLog message",
+                &InputFormat::Custom("%a %b %e %H:%M:%S %Y".to_string()),
+                &opts
+            ),
+            (
+                "",
+                Some(NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2022, 3, 6).unwrap(),
+                    NaiveTime::from_hms_opt(15, 32, 1).unwrap()
+                )),
+                "\tLog message"
+            )
+        );
+        // Timestamp must be followed by whitespace.
+        assert_eq!(
+            parse_line("2001-02-13 12:34:56", &InputFormat::Iso8601, &opts),
+            ("", None, "2001-02-13 12:34:56")
+        );
+        // Date only
+        assert_eq!(
+            parse_line("2001-02-13 Log message", &InputFormat::Iso8601, &opts),
+            ("", None, "2001-02-13 Log message")
+        );
+    }
+
+    #[test]
+    fn test_parse_line_located() {
+        let t = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2022, 3, 20).unwrap(),
+            NaiveTime::from_hms_opt(15, 32, 1).unwrap(),
+        );
+        let opts = ParseOptions {
+            locator: Locator::Regex(Regex::new(r"^\[[^]]*\] (.*?) -").unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_line(
+                "[host-3] 2022-03-20 15:32:01 - Log message",
+                &InputFormat::Iso8601,
+                &opts
+            ),
+            ("[host-3] ", Some(t), " - Log message")
+        );
+        assert_eq!(
+            parse_line("[host-3] foo - Log message", &InputFormat::Iso8601, &opts),
+            ("", None, "[host-3] foo - Log message")
+        );
+        let opts = ParseOptions {
+            locator: Locator::Field {
+                index: 2,
+                delimiter: None,
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_line(
+                "INFO 2022-03-20T15:32:01 Log message",
+                &InputFormat::Iso8601,
+                &opts
+            ),
+            ("INFO ", Some(t), " Log message")
+        );
+        let opts = ParseOptions {
+            locator: Locator::Field {
+                index: 3,
+                delimiter: Some('|'),
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_line(
+                "INFO|main|2022-03-20 15:32:01|Log message",
+                &InputFormat::Iso8601,
+                &opts
+            ),
+            ("INFO|main|", Some(t), "|Log message")
+        );
+    }
+
+    #[test]
+    fn test_parse_line_brackets() {
+        let opts = ParseOptions::default();
+        assert_eq!(
+            parse_line(
+                "[2022-03-20 15:32:01.462] Log message",
+                &InputFormat::Iso8601,
+                &opts
+            ),
+            (
+                "[",
+                Some(NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(2022, 3, 20).unwrap(),
+                    NaiveTime::from_hms_milli_opt(15, 32, 1, 462).unwrap()
+                )),
+                "] Log message"
+            )
+        );
+        assert_eq!(
+            parse_line("(1650400500.123456) can0", &InputFormat::Unix, &opts),
+            ("(", from_timestamp(1650400500, 123456000), ") can0")
+        );
+        assert_eq!(
+            parse_line("[   12.345678] Linux", &InputFormat::Unix, &opts),
+            ("[   ", from_timestamp(12, 345678000), "] Linux")
+        );
+        assert_eq!(
+            parse_line("<1650400500> Log message", &InputFormat::Unix, &opts),
+            ("<", from_timestamp(1650400500, 0), "> Log message")
+        );
+        assert_eq!(
+            parse_line("[main] Log message", &InputFormat::Unix, &opts),
+            ("", None, "[main] Log message")
+        );
+    }
+
+    #[test]
+    fn test_detect_format_located() {
+        let opts = ParseOptions {
+            locator: Locator::Field {
+                index: 2,
+                delimiter: None,
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            detect_format("INFO 2022-03-20 15:32:01 Log message", &opts),
+            Some(InputFormat::Iso8601)
+        );
+        assert_eq!(
+            detect_format("INFO 1650400500.123 Log message", &opts),
+            Some(InputFormat::Unix)
+        );
+        assert_eq!(
+            detect_format("2022-03-20T15:32:01 Log message", &opts),
+            None
+        );
     }
 
     #[test]
     fn test_detect_format() {
+        let opts = ParseOptions::default();
         assert_eq!(
-            detect_format("982240496.123 Log message"),
+            detect_format("982240496.123 Log message", &opts),
             Some(InputFormat::Unix)
         );
         assert_eq!(
-            detect_format("1650400500.123 Log message"),
+            detect_format("1650400500.123 Log message", &opts),
             Some(InputFormat::Unix)
         );
         assert_eq!(
-            detect_format("982240496123.456 Log message"),
+            detect_format("982240496123.456 Log message", &opts),
             Some(InputFormat::UnixMs)
         );
         assert_eq!(
-            detect_format("1650400500123.456 Log message"),
+            detect_format("1650400500123.456 Log message", &opts),
             Some(InputFormat::UnixMs)
         );
         assert_eq!(
-            detect_format("2001-12-13T12:34:56 Log message"),
+            detect_format("1650400500123456 Log message", &opts),
+            Some(InputFormat::UnixUs)
+        );
+        assert_eq!(
+            detect_format("100000000000000 Log message", &opts),
+            Some(InputFormat::UnixUs)
+        );
+        assert_eq!(
+            detect_format("1650400500123456789 Log message", &opts),
+            Some(InputFormat::UnixNs)
+        );
+        assert_eq!(
+            detect_format("0 Log message", &opts),
+            Some(InputFormat::Unix)
+        );
+        assert_eq!(
+            detect_format("2001-12-13T12:34:56 Log message", &opts),
+            Some(InputFormat::Iso8601)
+        );
+        assert_eq!(
+            detect_format("2001-12-13T12:34:56.123 Log message", &opts),
+            Some(InputFormat::Iso8601)
+        );
+        assert_eq!(
+            detect_format("2001-12-13T12:34:56Z Log message", &opts),
             Some(InputFormat::Iso8601)
         );
         assert_eq!(
-            detect_format("2001-12-13T12:34:56.123 Log message"),
+            detect_format("2001-12-13T12:34:56.123+02:00 Log message", &opts),
             Some(InputFormat::Iso8601)
         );
-        assert_eq!(detect_format("Log message"), None);
-        assert_eq!(detect_format("Logmessage"), None);
-        assert_eq!(detect_format(" Logmessage"), None);
-        assert_eq!(detect_format(" "), None);
-        assert_eq!(detect_format(""), None);
+        assert_eq!(
+            detect_format("2001-12-13T12:34:56.123-0800 Log message", &opts),
+            Some(InputFormat::Iso8601)
+        );
+        assert_eq!(
+            detect_format("2001-12-13 12:34:56.123 Log message", &opts),
+            Some(InputFormat::Iso8601)
+        );
+        assert_eq!(
+            detect_format("2001-12-13 12:34:56+02:00 Log message", &opts),
+            Some(InputFormat::Iso8601)
+        );
+        assert_eq!(
+            detect_format("2001-12-13 12:34:56,123 INFO Log message", &opts),
+            Some(InputFormat::Iso8601)
+        );
+        assert_eq!(
+            detect_format("[2001-12-13 12:34:56.123] Log message", &opts),
+            Some(InputFormat::Iso8601)
+        );
+        assert_eq!(
+            detect_format("[    1.123456] Log message", &opts),
+            Some(InputFormat::Unix)
+        );
+        assert_eq!(
+            detect_format("(1650400500.123456) Log message", &opts),
+            Some(InputFormat::Unix)
+        );
+        assert_eq!(
+            detect_format("<1650400500123> Log message", &opts),
+            Some(InputFormat::UnixMs)
+        );
+        assert_eq!(
+            detect_format("Mar 20 15:32:01 host app[123]: Log message", &opts),
+            Some(InputFormat::Syslog)
+        );
+        assert_eq!(
+            detect_format("Feb 29 15:32:01 host app[123]: Log message", &opts),
+            Some(InputFormat::Syslog)
+        );
+        assert_eq!(
+            detect_format("<13>1 2022-03-20T15:32:01.123Z host app - - msg", &opts),
+            Some(InputFormat::Iso8601)
+        );
+        assert_eq!(
+            detect_format("<34>Oct 11 22:14:15 mymachine su: msg", &opts),
+            Some(InputFormat::Syslog)
+        );
+        assert_eq!(
+            detect_format(
+                "127.0.0.1 - - [20/Mar/2022:15:32:01 +0000] \"GET / HTTP/1.1\" 200 512",
+                &opts
+            ),
+            Some(InputFormat::Clf)
+        );
+        // Only Common Log Format is looked for in the fourth field.
+        assert_eq!(detect_format("a b c 1650400500 x", &opts), None);
+        assert_eq!(detect_format("[main] Log message", &opts), None);
+        assert_eq!(detect_format("Log message 123 and more", &opts), None);
+        assert_eq!(detect_format("Log message", &opts), None);
+        assert_eq!(detect_format("Logmessage", &opts), None);
+        assert_eq!(detect_format(" Logmessage", &opts), None);
+        assert_eq!(detect_format(" ", &opts), None);
+        assert_eq!(detect_format("", &opts), None);
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let opts = ParseOptions {
+            year: 2022,
+            latest: None,
+            ..Default::default()
+        };
+        let expected = NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(2022, 3, 20).unwrap(),
+            NaiveTime::from_hms_opt(15, 32, 1).unwrap(),
+        );
+        assert_eq!(
+            parse_timestamp("2022-03-20 15:32:01", None, &opts),
+            Some(expected)
+        );
+        assert_eq!(
+            parse_timestamp("Mar 20 15:32:01", None, &opts),
+            Some(expected)
+        );
+        assert_eq!(parse_timestamp("1647790321", None, &opts), Some(expected));
+        // The given format goes before detection.
+        assert_eq!(
+            parse_timestamp("0", Some(&InputFormat::Epoc(expected)), &opts),
+            Some(expected)
+        );
+        assert_eq!(
+            parse_timestamp(
+                "20/Mar/2022:15:32:01 +0000",
+                Some(&InputFormat::Unix),
+                &opts
+            ),
+            Some(expected)
+        );
+        assert_eq!(parse_timestamp("15:32:01", None, &opts), None);
     }
 }
@@ -1,4 +1,6 @@
-use chrono::NaiveDateTime;
+use crate::{Error, Zone};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{Duration, NaiveDateTime};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Unit {
@@ -11,52 +13,121 @@ pub enum Unit {
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Precision(pub usize);
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum OutputFormat {
-    Iso8601 { prec: Precision, time_only: bool },
+    /// Wall-clock time in `zone`, optionally followed by the UTC offset. Fractional seconds are
+    /// separated by a comma instead of a dot if `comma` is set.
+    Iso8601 {
+        prec: Precision,
+        time_only: bool,
+        zone: Zone,
+        offset: bool,
+        comma: bool,
+    },
     Unix(Unit, Precision),
-    Delta(Unit, Precision),
+    /// Time since the previous line. Negative values, i.e. lines out of order, are prefixed with
+    /// "!" if `mark` is set.
+    Delta {
+        unit: Unit,
+        prec: Precision,
+        mark: bool,
+    },
     Elapsed(Unit, Precision),
+    /// RFC 5424 syslog timestamp in `zone`, with microsecond precision and UTC offset.
+    Rfc5424 {
+        zone: Zone,
+    },
+    /// strftime format, e.g. "%b %d %H:%M:%S", rendered in `zone`.
+    Custom {
+        fmt: String,
+        zone: Zone,
+    },
 }
 
-fn format_seconds(seconds: i64, nanos: u32, units: Unit, prec: Precision) -> String {
+/// Formats `seconds` plus `nanos` nanoseconds in given units. `seconds` may be negative, but
+/// `nanos` is always added, e.g. (-2, 500000000) is -1.5 seconds.
+///
+/// Excess digits are truncated towards zero, and the minus sign is left out if all the digits
+/// shown are zero.
+fn format_seconds(seconds: i64, nanos: u32, units: Unit, prec: Precision) -> Result<String, Error> {
+    if prec.0 > 9 {
+        return Err(Error::InvalidPrecision(prec.0));
+    }
     let prec = prec.0 as u32;
-    let nanos = nanos as i64;
-    assert!(prec <= 9);
 
-    let mag = 1000i64.pow(units as u32);
-    let rmag = 1000i64.pow(3 - units as u32);
+    // Format the magnitude and add the sign afterwards.
+    let (negative, seconds, nanos) = match (seconds < 0, nanos) {
+        (false, _) => (false, seconds as u64, nanos as u64),
+        (true, 0) => (true, seconds.unsigned_abs(), 0),
+        (true, _) => (
+            true,
+            seconds.unsigned_abs() - 1,
+            1_000_000_000 - nanos as u64,
+        ),
+    };
+
+    let mag = 1000u64.pow(units as u32);
+    let rmag = 1000u64.pow(3 - units as u32);
     let full = seconds as u128 * mag as u128 + (nanos / rmag) as u128;
     let frac = nanos % rmag;
     let frac_digits = 9 - units as u32 * 3;
 
-    if prec == 0 {
+    let s = if prec == 0 {
         format!("{}", full)
     } else if frac_digits > prec {
         format!(
             "{}.{:0width$}",
             full,
-            frac / 10i64.pow(frac_digits - prec),
+            frac / 10u64.pow(frac_digits - prec),
             width = prec as usize
         )
     } else {
         format!(
             "{}.{:0width$}",
             full,
-            frac * 10i64.pow(prec - frac_digits),
+            frac * 10u64.pow(prec - frac_digits),
             width = prec as usize
         )
-    }
+    };
+    Ok(
+        match negative && s.bytes().any(|c| matches!(c, b'1'..=b'9')) {
+            true => format!("-{}", s),
+            false => s,
+        },
+    )
 }
 
+/// Formats a duration, which may be negative, in given units.
+fn format_duration(d: Duration, units: Unit, prec: Precision) -> Result<String, Error> {
+    let ns = d.num_nanoseconds().ok_or(Error::DeltaOverflow)?;
+    format_seconds(
+        ns.div_euclid(1_000_000_000),
+        ns.rem_euclid(1_000_000_000) as u32,
+        units,
+        prec,
+    )
+}
+
+/// Formats datetime `t` according to given format. `prev_t` and `first_t` are the times of the
+/// previous and the first line, for relative formats.
 pub fn write(
-    format: OutputFormat,
+    format: &OutputFormat,
     t: NaiveDateTime,
     prev_t: Option<NaiveDateTime>,
     first_t: Option<NaiveDateTime>,
-) -> String {
-    match format {
-        OutputFormat::Iso8601 { prec, time_only } => {
+) -> Result<String, Error> {
+    Ok(match format {
+        OutputFormat::Iso8601 {
+            prec,
+            time_only,
+            zone,
+            offset,
+            comma,
+        } => {
+            if prec.0 > 9 {
+                return Err(Error::InvalidPrecision(prec.0));
+            }
+            let t = zone.from_utc(t);
             let mut s = t
                 .format(match time_only {
                     false => "%Y-%m-%dT%H:%M:%S%.9f",
@@ -67,24 +138,44 @@ pub fn write(
                 Precision(0) => s.truncate(s.len() - 10),
                 Precision(n) => s.truncate(s.len() - 9 + n),
             }
+            if *comma {
+                s = s.replacen('.', ",", 1);
+            }
+            if *offset {
+                s.push_str(&t.format("%:z").to_string());
+            }
             s
         }
         OutputFormat::Unix(unit, prec) => {
-            format_seconds(t.timestamp(), t.timestamp_subsec_nanos(), unit, prec)
+            let t = t.and_utc();
+            format_seconds(t.timestamp(), t.timestamp_subsec_nanos(), *unit, *prec)?
         }
-        OutputFormat::Delta(unit, prec) => {
-            let ns = (t - prev_t.unwrap_or(t))
-                .num_nanoseconds()
-                .expect("Too large delta");
-            format_seconds(ns / 1_000_000_000, (ns % 1_000_000_000) as u32, unit, prec)
+        OutputFormat::Delta { unit, prec, mark } => {
+            let s = format_duration(t - prev_t.unwrap_or(t), *unit, *prec)?;
+            // Values truncated to zero have no minus sign, and get no mark either.
+            match *mark && s.starts_with('-') {
+                true => format!("!{}", s),
+                false => s,
+            }
         }
         OutputFormat::Elapsed(unit, prec) => {
-            let ns = (t - first_t.unwrap_or(t))
-                .num_nanoseconds()
-                .expect("Too large delta");
-            format_seconds(ns / 1_000_000_000, (ns % 1_000_000_000) as u32, unit, prec)
+            format_duration(t - first_t.unwrap_or(t), *unit, *prec)?
         }
-    }
+        OutputFormat::Rfc5424 { zone } => {
+            let t = zone.from_utc(t);
+            match zone {
+                Zone::Utc => t.format("%Y-%m-%dT%H:%M:%S%.6fZ").to_string(),
+                _ => t.format("%Y-%m-%dT%H:%M:%S%.6f%:z").to_string(),
+            }
+        }
+        OutputFormat::Custom { fmt, zone } => {
+            let items = StrftimeItems::new(fmt);
+            if items.clone().any(|item| item == Item::Error) {
+                return Err(Error::InvalidFormat(fmt.clone()));
+            }
+            zone.from_utc(t).format_with_items(items).to_string()
+        }
+    })
 }
 
 #[cfg(test)]
@@ -94,8 +185,8 @@ mod tests {
 
     fn some_date() -> NaiveDateTime {
         NaiveDateTime::new(
-            NaiveDate::from_ymd(2001, 2, 15),
-            NaiveTime::from_hms_nano(12, 34, 56, 123_456_789),
+            NaiveDate::from_ymd_opt(2001, 2, 15).unwrap(),
+            NaiveTime::from_hms_nano_opt(12, 34, 56, 123_456_789).unwrap(),
         )
     }
 
@@ -103,244 +194,629 @@ mod tests {
     fn output_iso8601() {
         assert_eq!(
             write(
-                OutputFormat::Iso8601 {
+                &OutputFormat::Iso8601 {
                     prec: Precision(0),
-                    time_only: false
+                    time_only: false,
+                    zone: Zone::Utc,
+                    offset: false,
+                    comma: false,
                 },
                 some_date(),
                 None,
                 None
-            ),
+            )
+            .unwrap(),
             "2001-02-15T12:34:56"
         );
         assert_eq!(
             write(
-                OutputFormat::Iso8601 {
+                &OutputFormat::Iso8601 {
                     prec: Precision(1),
-                    time_only: false
+                    time_only: false,
+                    zone: Zone::Utc,
+                    offset: false,
+                    comma: false,
                 },
                 some_date(),
                 None,
                 None
-            ),
+            )
+            .unwrap(),
             "2001-02-15T12:34:56.1"
         );
         assert_eq!(
             write(
-                OutputFormat::Iso8601 {
+                &OutputFormat::Iso8601 {
                     prec: Precision(3),
-                    time_only: false
+                    time_only: false,
+                    zone: Zone::Utc,
+                    offset: false,
+                    comma: false,
                 },
                 some_date(),
                 None,
                 None
-            ),
+            )
+            .unwrap(),
             "2001-02-15T12:34:56.123"
         );
         assert_eq!(
             write(
-                OutputFormat::Iso8601 {
+                &OutputFormat::Iso8601 {
                     prec: Precision(0),
-                    time_only: true
+                    time_only: true,
+                    zone: Zone::Utc,
+                    offset: false,
+                    comma: false,
                 },
                 some_date(),
                 None,
                 None
-            ),
+            )
+            .unwrap(),
             "12:34:56"
         );
         assert_eq!(
             write(
-                OutputFormat::Iso8601 {
+                &OutputFormat::Iso8601 {
                     prec: Precision(3),
-                    time_only: true
+                    time_only: true,
+                    zone: Zone::Utc,
+                    offset: false,
+                    comma: false,
                 },
                 some_date(),
                 None,
                 None
-            ),
+            )
+            .unwrap(),
             "12:34:56.123"
         );
     }
 
+    #[test]
+    fn output_iso8601_comma() {
+        assert_eq!(
+            write(
+                &OutputFormat::Iso8601 {
+                    prec: Precision(3),
+                    time_only: false,
+                    zone: Zone::Utc,
+                    offset: false,
+                    comma: true,
+                },
+                some_date(),
+                None,
+                None
+            )
+            .unwrap(),
+            "2001-02-15T12:34:56,123"
+        );
+        assert_eq!(
+            write(
+                &OutputFormat::Iso8601 {
+                    prec: Precision(0),
+                    time_only: true,
+                    zone: Zone::Utc,
+                    offset: false,
+                    comma: true,
+                },
+                some_date(),
+                None,
+                None
+            )
+            .unwrap(),
+            "12:34:56"
+        );
+    }
+
+    #[test]
+    fn output_iso8601_zone() {
+        let helsinki = Zone::Named(chrono_tz::Europe::Helsinki);
+        assert_eq!(
+            write(
+                &OutputFormat::Iso8601 {
+                    prec: Precision(0),
+                    time_only: false,
+                    zone: Zone::Utc,
+                    offset: true,
+                    comma: false,
+                },
+                some_date(),
+                None,
+                None
+            )
+            .unwrap(),
+            "2001-02-15T12:34:56+00:00"
+        );
+        assert_eq!(
+            write(
+                &OutputFormat::Iso8601 {
+                    prec: Precision(3),
+                    time_only: false,
+                    zone: helsinki,
+                    offset: false,
+                    comma: false,
+                },
+                some_date(),
+                None,
+                None
+            )
+            .unwrap(),
+            "2001-02-15T14:34:56.123"
+        );
+        assert_eq!(
+            write(
+                &OutputFormat::Iso8601 {
+                    prec: Precision(3),
+                    time_only: false,
+                    zone: helsinki,
+                    offset: true,
+                    comma: false,
+                },
+                some_date(),
+                None,
+                None
+            )
+            .unwrap(),
+            "2001-02-15T14:34:56.123+02:00"
+        );
+        assert_eq!(
+            write(
+                &OutputFormat::Iso8601 {
+                    prec: Precision(0),
+                    time_only: true,
+                    zone: helsinki,
+                    offset: true,
+                    comma: false,
+                },
+                some_date(),
+                None,
+                None
+            )
+            .unwrap(),
+            "14:34:56+02:00"
+        );
+    }
+
+    #[test]
+    fn output_rfc5424() {
+        assert_eq!(
+            write(
+                &OutputFormat::Rfc5424 { zone: Zone::Utc },
+                some_date(),
+                None,
+                None
+            )
+            .unwrap(),
+            "2001-02-15T12:34:56.123456Z"
+        );
+        assert_eq!(
+            write(
+                &OutputFormat::Rfc5424 {
+                    zone: Zone::Named(chrono_tz::America::Los_Angeles)
+                },
+                some_date(),
+                None,
+                None
+            )
+            .unwrap(),
+            "2001-02-15T04:34:56.123456-08:00"
+        );
+    }
+
+    #[test]
+    fn output_custom() {
+        assert_eq!(
+            write(
+                &OutputFormat::Custom {
+                    fmt: "%b %d %H:%M:%S".to_string(),
+                    zone: Zone::Utc
+                },
+                some_date(),
+                None,
+                None
+            )
+            .unwrap(),
+            "Feb 15 12:34:56"
+        );
+        assert_eq!(
+            write(
+                &OutputFormat::Custom {
+                    fmt: "%Y%m%d-%H%M%S%.3f".to_string(),
+                    zone: Zone::Utc
+                },
+                some_date(),
+                None,
+                None
+            )
+            .unwrap(),
+            "20010215-123456.123"
+        );
+        assert_eq!(
+            write(
+                &OutputFormat::Custom {
+                    fmt: "%H:%M %z".to_string(),
+                    zone: Zone::Named(chrono_tz::Europe::Helsinki)
+                },
+                some_date(),
+                None,
+                None
+            )
+            .unwrap(),
+            "14:34 +0200"
+        );
+    }
+
     #[test]
     fn output_unix() {
         assert_eq!(
             write(
-                OutputFormat::Unix(Unit::Seconds, Precision(0)),
+                &OutputFormat::Unix(Unit::Seconds, Precision(0)),
                 some_date(),
                 None,
                 None
-            ),
+            )
+            .unwrap(),
             "982240496"
         );
         assert_eq!(
             write(
-                OutputFormat::Unix(Unit::Milliseconds, Precision(0)),
+                &OutputFormat::Unix(Unit::Milliseconds, Precision(0)),
                 some_date(),
                 None,
                 None
-            ),
+            )
+            .unwrap(),
             "982240496123"
         );
         assert_eq!(
             write(
-                OutputFormat::Unix(Unit::Microseconds, Precision(3)),
+                &OutputFormat::Unix(Unit::Microseconds, Precision(3)),
                 some_date(),
                 None,
                 None
-            ),
+            )
+            .unwrap(),
             "982240496123456.789"
         );
         assert_eq!(
             write(
-                OutputFormat::Unix(Unit::Nanoseconds, Precision(9)),
+                &OutputFormat::Unix(Unit::Nanoseconds, Precision(9)),
                 some_date(),
                 None,
                 None
-            ),
+            )
+            .unwrap(),
             "982240496123456789.000000000"
         );
+        // Before 1970
+        assert_eq!(
+            write(
+                &OutputFormat::Unix(Unit::Milliseconds, Precision(3)),
+                NaiveDateTime::new(
+                    NaiveDate::from_ymd_opt(1969, 12, 31).unwrap(),
+                    NaiveTime::from_hms_milli_opt(23, 59, 58, 500).unwrap(),
+                ),
+                None,
+                None
+            )
+            .unwrap(),
+            "-1500.000"
+        );
     }
 
     #[test]
     fn output_delta() {
         assert_eq!(
             write(
-                OutputFormat::Delta(Unit::Seconds, Precision(0)),
+                &OutputFormat::Delta {
+                    unit: Unit::Seconds,
+                    prec: Precision(0),
+                    mark: false,
+                },
                 some_date(),
                 None,
                 None
-            ),
+            )
+            .unwrap(),
             "0"
         );
         assert_eq!(
             write(
-                OutputFormat::Delta(Unit::Seconds, Precision(0)),
+                &OutputFormat::Delta {
+                    unit: Unit::Seconds,
+                    prec: Precision(0),
+                    mark: false,
+                },
                 some_date(),
                 Some(some_date() - Duration::seconds(130)),
                 None,
-            ),
+            )
+            .unwrap(),
             "130"
         );
         assert_eq!(
             write(
-                OutputFormat::Delta(Unit::Milliseconds, Precision(0)),
+                &OutputFormat::Delta {
+                    unit: Unit::Milliseconds,
+                    prec: Precision(0),
+                    mark: false,
+                },
                 some_date(),
                 Some(some_date() - Duration::milliseconds(130)),
                 None,
-            ),
+            )
+            .unwrap(),
             "130"
         );
         assert_eq!(
             write(
-                OutputFormat::Delta(Unit::Microseconds, Precision(0)),
+                &OutputFormat::Delta {
+                    unit: Unit::Microseconds,
+                    prec: Precision(0),
+                    mark: false,
+                },
                 some_date(),
                 Some(some_date() - Duration::microseconds(130)),
                 None,
-            ),
+            )
+            .unwrap(),
             "130"
         );
         assert_eq!(
             write(
-                OutputFormat::Delta(Unit::Nanoseconds, Precision(3)),
+                &OutputFormat::Delta {
+                    unit: Unit::Nanoseconds,
+                    prec: Precision(3),
+                    mark: false,
+                },
                 some_date(),
                 Some(some_date() - Duration::nanoseconds(130)),
                 None,
-            ),
+            )
+            .unwrap(),
             "130.000"
         );
+        assert_eq!(
+            write(
+                &OutputFormat::Delta {
+                    unit: Unit::Seconds,
+                    prec: Precision(3),
+                    mark: false,
+                },
+                some_date(),
+                Some(some_date() + Duration::milliseconds(1250)),
+                None,
+            )
+            .unwrap(),
+            "-1.250"
+        );
+    }
+
+    #[test]
+    fn output_delta_mark() {
+        assert_eq!(
+            write(
+                &OutputFormat::Delta {
+                    unit: Unit::Milliseconds,
+                    prec: Precision(0),
+                    mark: true,
+                },
+                some_date(),
+                Some(some_date() + Duration::milliseconds(1250)),
+                None,
+            )
+            .unwrap(),
+            "!-1250"
+        );
+        assert_eq!(
+            write(
+                &OutputFormat::Delta {
+                    unit: Unit::Milliseconds,
+                    prec: Precision(0),
+                    mark: true,
+                },
+                some_date(),
+                Some(some_date() - Duration::milliseconds(1250)),
+                None,
+            )
+            .unwrap(),
+            "1250"
+        );
+        assert_eq!(
+            write(
+                &OutputFormat::Delta {
+                    unit: Unit::Milliseconds,
+                    prec: Precision(0),
+                    mark: true,
+                },
+                some_date(),
+                Some(some_date()),
+                None,
+            )
+            .unwrap(),
+            "0"
+        );
+        assert_eq!(
+            write(
+                &OutputFormat::Delta {
+                    unit: Unit::Milliseconds,
+                    prec: Precision(0),
+                    mark: true,
+                },
+                some_date(),
+                Some(some_date() + Duration::microseconds(250)),
+                None,
+            )
+            .unwrap(),
+            "0"
+        );
     }
 
     #[test]
     fn output_elapsed() {
         assert_eq!(
             write(
-                OutputFormat::Elapsed(Unit::Seconds, Precision(0)),
+                &OutputFormat::Elapsed(Unit::Seconds, Precision(0)),
                 some_date(),
                 None,
                 None
-            ),
+            )
+            .unwrap(),
             "0"
         );
         assert_eq!(
             write(
-                OutputFormat::Elapsed(Unit::Seconds, Precision(0)),
+                &OutputFormat::Elapsed(Unit::Seconds, Precision(0)),
                 some_date(),
                 None,
                 Some(some_date()),
-            ),
+            )
+            .unwrap(),
             "0"
         );
         assert_eq!(
             write(
-                OutputFormat::Elapsed(Unit::Seconds, Precision(3)),
+                &OutputFormat::Elapsed(Unit::Seconds, Precision(3)),
                 some_date(),
                 None,
                 Some(some_date() - Duration::milliseconds(10123)),
-            ),
+            )
+            .unwrap(),
             "10.123"
         );
     }
 
+    #[test]
+    fn output_errors() {
+        assert_eq!(
+            write(
+                &OutputFormat::Delta {
+                    unit: Unit::Seconds,
+                    prec: Precision(0),
+                    mark: false,
+                },
+                some_date(),
+                Some(some_date() - Duration::days(300 * 365)),
+                None,
+            ),
+            Err(Error::DeltaOverflow)
+        );
+        assert_eq!(
+            write(
+                &OutputFormat::Unix(Unit::Seconds, Precision(10)),
+                some_date(),
+                None,
+                None
+            ),
+            Err(Error::InvalidPrecision(10))
+        );
+        assert_eq!(
+            write(
+                &OutputFormat::Iso8601 {
+                    prec: Precision(10),
+                    time_only: false,
+                    zone: Zone::Utc,
+                    offset: false,
+                    comma: false,
+                },
+                some_date(),
+                None,
+                None
+            ),
+            Err(Error::InvalidPrecision(10))
+        );
+        assert_eq!(
+            write(
+                &OutputFormat::Custom {
+                    fmt: "%H:%M %Q".to_string(),
+                    zone: Zone::Utc
+                },
+                some_date(),
+                None,
+                None
+            ),
+            Err(Error::InvalidFormat("%H:%M %Q".to_string()))
+        );
+    }
+
     mod test_format_seconds {
         use super::*;
 
         #[test]
         fn integer_seconds() {
-            assert_eq!("0", format_seconds(0, 0, Unit::Seconds, Precision(0)));
-            assert_eq!("123", format_seconds(123, 0, Unit::Seconds, Precision(0)));
+            assert_eq!(
+                "0",
+                format_seconds(0, 0, Unit::Seconds, Precision(0)).unwrap()
+            );
+            assert_eq!(
+                "123",
+                format_seconds(123, 0, Unit::Seconds, Precision(0)).unwrap()
+            );
             assert_eq!(
                 "42",
-                format_seconds(42, 123_456_789, Unit::Seconds, Precision(0))
+                format_seconds(42, 123_456_789, Unit::Seconds, Precision(0)).unwrap()
+            );
+            assert_eq!(
+                "0",
+                format_seconds(0, 456_000, Unit::Seconds, Precision(0)).unwrap()
             );
-            assert_eq!("0", format_seconds(0, 456_000, Unit::Seconds, Precision(0)));
             assert_eq!(
                 "10000000000",
-                format_seconds(10_000_000_000, 123_456_789, Unit::Seconds, Precision(0))
+                format_seconds(10_000_000_000, 123_456_789, Unit::Seconds, Precision(0)).unwrap()
             );
         }
 
         #[test]
         fn integer_microseconds() {
-            assert_eq!("0", format_seconds(0, 0, Unit::Microseconds, Precision(0)));
+            assert_eq!(
+                "0",
+                format_seconds(0, 0, Unit::Microseconds, Precision(0)).unwrap()
+            );
             assert_eq!(
                 "123000000",
-                format_seconds(123, 0, Unit::Microseconds, Precision(0))
+                format_seconds(123, 0, Unit::Microseconds, Precision(0)).unwrap()
             );
             assert_eq!(
                 "456",
-                format_seconds(0, 456_000, Unit::Microseconds, Precision(0))
+                format_seconds(0, 456_000, Unit::Microseconds, Precision(0)).unwrap()
             );
             assert_eq!(
                 "42123456",
-                format_seconds(42, 123_456_789, Unit::Microseconds, Precision(0))
+                format_seconds(42, 123_456_789, Unit::Microseconds, Precision(0)).unwrap()
             );
         }
 
         #[test]
         fn fractional_seconds() {
-            assert_eq!("0.0", format_seconds(0, 0, Unit::Seconds, Precision(1)));
-            assert_eq!("0.000", format_seconds(0, 0, Unit::Seconds, Precision(3)));
+            assert_eq!(
+                "0.0",
+                format_seconds(0, 0, Unit::Seconds, Precision(1)).unwrap()
+            );
+            assert_eq!(
+                "0.000",
+                format_seconds(0, 0, Unit::Seconds, Precision(3)).unwrap()
+            );
             assert_eq!(
                 "0.000000000",
-                format_seconds(0, 0, Unit::Seconds, Precision(9))
+                format_seconds(0, 0, Unit::Seconds, Precision(9)).unwrap()
             );
             assert_eq!(
                 "42.123",
-                format_seconds(42, 123_456_789, Unit::Seconds, Precision(3))
+                format_seconds(42, 123_456_789, Unit::Seconds, Precision(3)).unwrap()
             );
             assert_eq!(
                 "42.123456789",
-                format_seconds(42, 123_456_789, Unit::Seconds, Precision(9))
+                format_seconds(42, 123_456_789, Unit::Seconds, Precision(9)).unwrap()
             );
             assert_eq!(
                 "0.000456",
-                format_seconds(0, 456_000, Unit::Seconds, Precision(6))
+                format_seconds(0, 456_000, Unit::Seconds, Precision(6)).unwrap()
             );
             assert_eq!(
                 "10000000000.123456789",
-                format_seconds(10_000_000_000, 123_456_789, Unit::Seconds, Precision(9))
+                format_seconds(10_000_000_000, 123_456_789, Unit::Seconds, Precision(9)).unwrap()
             );
         }
 
@@ -348,15 +824,15 @@ mod tests {
         fn fractional_microseconds() {
             assert_eq!(
                 "0.0",
-                format_seconds(0, 0, Unit::Microseconds, Precision(1))
+                format_seconds(0, 0, Unit::Microseconds, Precision(1)).unwrap()
             );
             assert_eq!(
                 "0.000",
-                format_seconds(0, 0, Unit::Microseconds, Precision(3))
+                format_seconds(0, 0, Unit::Microseconds, Precision(3)).unwrap()
             );
             assert_eq!(
                 "0.000000000",
-                format_seconds(0, 0, Unit::Microseconds, Precision(9))
+                format_seconds(0, 0, Unit::Microseconds, Precision(9)).unwrap()
             );
             assert_eq!(
                 "10000000000123456.789000000",
@@ -366,6 +842,40 @@ mod tests {
                     Unit::Microseconds,
                     Precision(9)
                 )
+                .unwrap()
+            );
+        }
+
+        #[test]
+        fn negative() {
+            assert_eq!(
+                "-1",
+                format_seconds(-1, 0, Unit::Seconds, Precision(0)).unwrap()
+            );
+            assert_eq!(
+                "-1.5",
+                format_seconds(-2, 500_000_000, Unit::Seconds, Precision(1)).unwrap()
+            );
+            assert_eq!(
+                "-0.250",
+                format_seconds(-1, 750_000_000, Unit::Seconds, Precision(3)).unwrap()
+            );
+            assert_eq!(
+                "-250",
+                format_seconds(-1, 750_000_000, Unit::Milliseconds, Precision(0)).unwrap()
+            );
+            assert_eq!(
+                "-9223372036854775808",
+                format_seconds(i64::MIN, 0, Unit::Seconds, Precision(0)).unwrap()
+            );
+            // Truncated to zero
+            assert_eq!(
+                "0",
+                format_seconds(-1, 750_000_000, Unit::Seconds, Precision(0)).unwrap()
+            );
+            assert_eq!(
+                "0.00",
+                format_seconds(-1, 999_999_999, Unit::Seconds, Precision(2)).unwrap()
             );
         }
 
@@ -374,6 +884,7 @@ mod tests {
             assert_eq!(
                 "10000000000123456789.000000000",
                 format_seconds(10_000_000_000, 123_456_789, Unit::Nanoseconds, Precision(9))
+                    .unwrap()
             );
         }
     }
@@ -0,0 +1,204 @@
+use chrono::{
+    DateTime, Duration, FixedOffset, Local, LocalResult, NaiveDateTime, Offset, TimeZone,
+};
+use chrono_tz::Tz;
+use std::str::FromStr;
+
+/// Time zone used for interpreting or displaying timestamps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Zone {
+    Utc,
+    /// Time zone of the local machine.
+    Local,
+    /// IANA time zone, e.g. "Europe/Helsinki".
+    Named(Tz),
+}
+
+/// How to resolve local times that are ambiguous or nonexistent due to DST transitions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DstPolicy {
+    /// Choose the earlier of the two possible instants.
+    #[default]
+    Earliest,
+    /// Choose the later of the two possible instants.
+    Latest,
+    /// Treat the time as invalid.
+    Reject,
+}
+
+impl FromStr for DstPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<DstPolicy, String> {
+        match s {
+            "earliest" => Ok(DstPolicy::Earliest),
+            "latest" => Ok(DstPolicy::Latest),
+            "reject" => Ok(DstPolicy::Reject),
+            _ => Err(format!("Invalid DST policy {:?}", s)),
+        }
+    }
+}
+
+/// Converts local time of `tz` to UTC.
+///
+/// A time repeated when clocks are turned back maps to two instants. A time skipped when clocks
+/// are turned forward is interpreted using the offsets before and after the transition, which
+/// also gives two instants. `policy` chooses between them.
+fn local_to_utc<T: TimeZone>(tz: &T, t: NaiveDateTime, policy: DstPolicy) -> Option<NaiveDateTime> {
+    let (earliest, latest) = match tz.from_local_datetime(&t) {
+        LocalResult::Single(t) => return Some(t.naive_utc()),
+        LocalResult::Ambiguous(a, b) => (a.naive_utc(), b.naive_utc()),
+        LocalResult::None => {
+            // Assumes there's only one transition within a day.
+            let before = tz.offset_from_utc_datetime(&(t - Duration::days(1))).fix();
+            let after = tz.offset_from_utc_datetime(&(t + Duration::days(1))).fix();
+            let a = t - Duration::seconds(before.local_minus_utc().into());
+            let b = t - Duration::seconds(after.local_minus_utc().into());
+            (a.min(b), a.max(b))
+        }
+    };
+    match policy {
+        DstPolicy::Earliest => Some(earliest),
+        DstPolicy::Latest => Some(latest),
+        DstPolicy::Reject => None,
+    }
+}
+
+impl Zone {
+    /// Converts wall-clock time of this zone to UTC. Returns None if the time is ambiguous or
+    /// nonexistent and `policy` rejects it.
+    pub fn to_utc(&self, t: NaiveDateTime, policy: DstPolicy) -> Option<NaiveDateTime> {
+        match self {
+            Zone::Utc => Some(t),
+            Zone::Local => local_to_utc(&Local, t, policy),
+            Zone::Named(tz) => local_to_utc(tz, t, policy),
+        }
+    }
+
+    /// Converts UTC datetime to the wall-clock time of this zone.
+    pub fn from_utc(&self, t: NaiveDateTime) -> DateTime<FixedOffset> {
+        match self {
+            Zone::Utc => t.and_utc().fixed_offset(),
+            Zone::Local => Local.from_utc_datetime(&t).fixed_offset(),
+            Zone::Named(tz) => tz.from_utc_datetime(&t).fixed_offset(),
+        }
+    }
+}
+
+impl FromStr for Zone {
+    type Err = String;
+
+    /// Parses "utc", "local" or an IANA time zone name.
+    fn from_str(s: &str) -> Result<Zone, String> {
+        match s {
+            "utc" | "UTC" => Ok(Zone::Utc),
+            "local" => Ok(Zone::Local),
+            _ => s
+                .parse::<Tz>()
+                .map(Zone::Named)
+                .map_err(|_| format!("Unknown time zone {:?}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, NaiveTime};
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> NaiveDateTime {
+        NaiveDateTime::new(
+            NaiveDate::from_ymd_opt(y, mo, d).unwrap(),
+            NaiveTime::from_hms_opt(h, mi, 0).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("utc".parse(), Ok(Zone::Utc));
+        assert_eq!("local".parse(), Ok(Zone::Local));
+        assert_eq!(
+            "Europe/Helsinki".parse(),
+            Ok(Zone::Named(chrono_tz::Europe::Helsinki))
+        );
+        assert!("Europe/Nowhere".parse::<Zone>().is_err());
+    }
+
+    #[test]
+    fn test_from_utc() {
+        let t = Zone::Utc.from_utc(at(2022, 3, 20, 12, 0));
+        assert_eq!(t.naive_local(), at(2022, 3, 20, 12, 0));
+        assert_eq!(t.offset().local_minus_utc(), 0);
+
+        // Winter time
+        let t = Zone::Named(chrono_tz::Europe::Helsinki).from_utc(at(2022, 3, 20, 12, 0));
+        assert_eq!(t.naive_local(), at(2022, 3, 20, 14, 0));
+        assert_eq!(t.offset().local_minus_utc(), 2 * 3600);
+
+        // Summer time
+        let t = Zone::Named(chrono_tz::Europe::Helsinki).from_utc(at(2022, 6, 20, 12, 0));
+        assert_eq!(t.naive_local(), at(2022, 6, 20, 15, 0));
+        assert_eq!(t.offset().local_minus_utc(), 3 * 3600);
+    }
+
+    #[test]
+    fn test_to_utc() {
+        let helsinki = Zone::Named(chrono_tz::Europe::Helsinki);
+        assert_eq!(
+            Zone::Utc.to_utc(at(2022, 3, 20, 12, 0), DstPolicy::Reject),
+            Some(at(2022, 3, 20, 12, 0))
+        );
+        assert_eq!(
+            helsinki.to_utc(at(2022, 3, 20, 12, 0), DstPolicy::Reject),
+            Some(at(2022, 3, 20, 10, 0))
+        );
+        assert_eq!(
+            helsinki.to_utc(at(2022, 6, 20, 12, 0), DstPolicy::Reject),
+            Some(at(2022, 6, 20, 9, 0))
+        );
+    }
+
+    #[test]
+    fn test_to_utc_dst_gap() {
+        // Clocks were turned from 03:00 to 04:00 on 2022-03-27 in Helsinki.
+        let helsinki = Zone::Named(chrono_tz::Europe::Helsinki);
+        assert_eq!(
+            helsinki.to_utc(at(2022, 3, 27, 3, 30), DstPolicy::Earliest),
+            Some(at(2022, 3, 27, 0, 30))
+        );
+        assert_eq!(
+            helsinki.to_utc(at(2022, 3, 27, 3, 30), DstPolicy::Latest),
+            Some(at(2022, 3, 27, 1, 30))
+        );
+        assert_eq!(
+            helsinki.to_utc(at(2022, 3, 27, 3, 30), DstPolicy::Reject),
+            None
+        );
+    }
+
+    #[test]
+    fn test_to_utc_dst_ambiguous() {
+        // Clocks were turned from 04:00 to 03:00 on 2022-10-30 in Helsinki.
+        let helsinki = Zone::Named(chrono_tz::Europe::Helsinki);
+        assert_eq!(
+            helsinki.to_utc(at(2022, 10, 30, 3, 30), DstPolicy::Earliest),
+            Some(at(2022, 10, 30, 0, 30))
+        );
+        assert_eq!(
+            helsinki.to_utc(at(2022, 10, 30, 3, 30), DstPolicy::Latest),
+            Some(at(2022, 10, 30, 1, 30))
+        );
+        assert_eq!(
+            helsinki.to_utc(at(2022, 10, 30, 3, 30), DstPolicy::Reject),
+            None
+        );
+    }
+
+    #[test]
+    fn test_dst_policy_from_str() {
+        assert_eq!("earliest".parse(), Ok(DstPolicy::Earliest));
+        assert_eq!("latest".parse(), Ok(DstPolicy::Latest));
+        assert_eq!("reject".parse(), Ok(DstPolicy::Reject));
+        assert!("first".parse::<DstPolicy>().is_err());
+    }
+}